@@ -1,54 +1,471 @@
 // L2.1
-// cargo run --bin t1 -- [-c|-l|-w] <filename>
-// t1.exe [-c|-l|-w] <filename>
+// https://linux.die.net/man/1/wc
+// cargo run --bin t1 -- [-clmwL] [FILE]...
+// echo "hello world" | cargo run --bin t1 -- -lw
+// cargo run --release --bin t1 -- --threads 8 big.log
 
-use std::{env, fs};
+/*
+
+Usage: t1.exe [OPTION]... [FILE]...
+
+Без файлов (или с файлом "-") читает STDIN.
+Без флагов печатает строки, слова и байты (-lwc).
+
+Options:
+  -c, --bytes            Количество байт
+  -m, --chars            Количество символов (UTF-8)
+  -l, --lines            Количество строк
+  -w, --words            Количество слов
+  -L, --max-line-length  Длина самой длинной строки
+      --threads <N>      Считать обычные файлы в N потоков [default: 1]
+
+*/
+
+use std::{
+    env, fs,
+    io::{self, Read, Seek, SeekFrom},
+    process, thread,
+};
+
+const BUFFER_SIZE: usize = 64 * 1024;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let (flag, file) = parse_arguments(&args);
-    let content = fs::read_to_string(file).expect("Couldn't open the file");
-
-    match flag {
-        Flag::C => println!("{}", content.chars().count()),
-        Flag::L => println!("{}", content.lines().count()),
-        Flag::W => println!("{}", content.split_whitespace().count()),
-    };
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (options, mut files) = parse_arguments(&args);
+    if files.is_empty() {
+        files.push("-");
+    }
+
+    let width = number_width(&options, &files);
+    let mut total = Counts::default();
+    let mut failed = false;
+
+    for file in files.iter() {
+        match count_file(file, &options) {
+            Ok(counts) => {
+                print_counts(&counts, &options, width, file);
+                total.merge(&counts);
+            }
+            Err(err) => {
+                eprintln!("t1: {file}: {err}");
+                failed = true;
+            }
+        }
+    }
+
+    if files.len() > 1 {
+        print_counts(&total, &options, width, "total");
+    }
+
+    if failed {
+        process::exit(1);
+    }
 }
 
-// Используем перечисление для точной обработки и удобства расширения
-// При добавлении новых флагов, компилятор сообщит о необходимости обработать новые флаги в ветках match
-enum Flag {
-    C,
-    L,
-    W,
+/// Набор выводимых колонок.
+/// Порядок вывода фиксирован и совпадает с GNU wc: строки, слова, символы, байты, длина строки
+#[derive(Default)]
+struct Options {
+    lines: bool,
+    words: bool,
+    chars: bool,
+    bytes: bool,
+    max_line_length: bool,
+    threads: usize,
+}
+
+impl Options {
+    fn columns(&self) -> usize {
+        [
+            self.lines,
+            self.words,
+            self.chars,
+            self.bytes,
+            self.max_line_length,
+        ]
+        .iter()
+        .filter(|&&column| column)
+        .count()
+    }
 }
 
 // Парсинг и валидация аргументов командной строки
-fn parse_arguments<'a>(args: &'a [String]) -> (Flag, &'a str) {
-    if args.len() < 2 || 3 < args.len() {
-        eprintln!("Wrong arguments. Usage: [-c|-l|-w] <filename>");
-        std::process::exit(1);
+// Короткие флаги можно объединять: -lw == -l -w
+fn parse_arguments(args: &[String]) -> (Options, Vec<&str>) {
+    let mut options = Options {
+        threads: 1,
+        ..Default::default()
+    };
+    let mut files = vec![];
+    let mut only_files = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if only_files || arg == "-" || !arg.starts_with('-') {
+            files.push(arg.as_str());
+            continue;
+        }
+
+        match arg.as_str() {
+            "--" => only_files = true,
+            "--threads" => options.threads = parse_threads(args.next().map(String::as_str)),
+            long if long.starts_with("--threads=") => {
+                options.threads = parse_threads(long.strip_prefix("--threads="))
+            }
+            "--bytes" => options.bytes = true,
+            "--chars" => options.chars = true,
+            "--lines" => options.lines = true,
+            "--words" => options.words = true,
+            "--max-line-length" => options.max_line_length = true,
+            long if long.starts_with("--") => usage_error(&format!("unknown option '{long}'")),
+            short => {
+                for flag in short.chars().skip(1) {
+                    match flag {
+                        'c' => options.bytes = true,
+                        'm' => options.chars = true,
+                        'l' => options.lines = true,
+                        'w' => options.words = true,
+                        'L' => options.max_line_length = true,
+                        _ => usage_error(&format!("unknown flag '-{flag}'")),
+                    }
+                }
+            }
+        }
+    }
+
+    // Поведение по умолчанию
+    if options.columns() == 0 {
+        options.lines = true;
+        options.words = true;
+        options.bytes = true;
+    }
+
+    (options, files)
+}
+
+fn parse_threads(value: Option<&str>) -> usize {
+    match value.map(str::parse::<usize>) {
+        Some(Ok(threads)) if threads > 0 => threads,
+        _ => usage_error("invalid number of threads"),
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("t1: {message}. Usage: [-clmwL] [FILE]...");
+    process::exit(1);
+}
+
+/// Результаты подсчета для одного источника
+#[derive(Default, Debug, Clone, PartialEq)]
+struct Counts {
+    lines: usize,
+    words: usize,
+    chars: usize,
+    bytes: usize,
+    max_line_length: usize,
+}
+
+impl Counts {
+    fn merge(&mut self, other: &Counts) {
+        self.lines += other.lines;
+        self.words += other.words;
+        self.chars += other.chars;
+        self.bytes += other.bytes;
+        self.max_line_length = self.max_line_length.max(other.max_line_length);
+    }
+}
+
+/// Потоковый счетчик: принимает данные кусками произвольного размера.
+/// Состояние между кусками (внутри слова, позиция в строке) сохраняется,
+/// поэтому результат не зависит от границ буфера
+#[derive(Default)]
+struct Counter {
+    counts: Counts,
+    in_word: bool,
+    line_position: usize,
+}
+
+impl Counter {
+    fn update(&mut self, chunk: &[u8]) {
+        let counts = &mut self.counts;
+        counts.bytes += chunk.len();
+
+        for &byte in chunk {
+            if is_space(byte) {
+                self.in_word = false;
+            } else if !self.in_word {
+                self.in_word = true;
+                counts.words += 1;
+            }
+
+            // Байты продолжения UTF-8 (10xxxxxx) не начинают новый символ
+            if byte & 0xC0 == 0x80 {
+                continue;
+            }
+            counts.chars += 1;
+
+            match byte {
+                b'\n' | b'\r' | b'\x0c' => {
+                    if byte == b'\n' {
+                        counts.lines += 1;
+                    }
+                    counts.max_line_length = counts.max_line_length.max(self.line_position);
+                    self.line_position = 0;
+                }
+                b'\t' => self.line_position += 8 - self.line_position % 8,
+                byte if byte.is_ascii_control() => (),
+                _ => self.line_position += 1,
+            }
+        }
     }
 
-    let flag;
-    let file;
+    fn finish(mut self) -> Counts {
+        self.counts.max_line_length = self.counts.max_line_length.max(self.line_position);
+        self.counts
+    }
+}
 
-    if args.len() == 2 {
-        flag = Flag::C;
-        file = &args[1];
+fn is_space(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r' | b'\x0b' | b'\x0c')
+}
+
+/// Подсчет с постоянным расходом памяти: файл читается кусками по BUFFER_SIZE байт
+fn count_reader(mut reader: impl Read) -> io::Result<Counts> {
+    let mut counter = Counter::default();
+    let mut buffer = vec![0; BUFFER_SIZE];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(bytes) => counter.update(&buffer[..bytes]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(counter.finish())
+}
+
+fn count_file(file: &str, options: &Options) -> io::Result<Counts> {
+    if file == "-" {
+        return count_reader(io::stdin().lock());
+    }
+
+    // Ширина строки с табуляцией зависит от позиции в строке,
+    // поэтому -L считается только последовательно
+    let handle = fs::File::open(file)?;
+    let metadata = handle.metadata()?;
+    if options.threads > 1 && !options.max_line_length && metadata.is_file() {
+        count_parallel(file, metadata.len(), options.threads)
     } else {
-        flag = match args[1].as_str() {
-            "-c" => Flag::C,
-            "-l" => Flag::L,
-            "-w" => Flag::W,
-            _ => {
-                eprintln!("Unknown flag. Awailable flags: '-c', '-w', '-l'");
-                std::process::exit(1);
+        count_reader(handle)
+    }
+}
+
+/// Результат подсчета для одного куска файла
+/// + первый байт, чтобы склеить слово на границе кусков
+struct Partition {
+    counter: Counter,
+    first: Option<u8>,
+}
+
+/// Параллельный подсчет строк, слов, символов и байт.
+/// Файл делится на равные куски (как в L2.7), но каждый поток открывает
+/// собственный дескриптор и читает свой кусок буфером BUFFER_SIZE.
+/// При объединении по порядку кусков слово, разрезанное границей, засчитывается один раз.
+/// Символы считаются по первым байтам UTF-8 и от границ не зависят
+fn count_parallel(file: &str, size: u64, threads: usize) -> io::Result<Counts> {
+    let bytes_per_thread = size / threads as u64;
+    let bytes_remains = size % threads as u64;
+
+    let partitions = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads as u64)
+            .map(|i| {
+                let start = i * bytes_per_thread;
+                let length = if i + 1 == threads as u64 {
+                    bytes_per_thread + bytes_remains
+                } else {
+                    bytes_per_thread
+                };
+                scope.spawn(move || count_partition(file, start, length))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Counting thread panicked"))
+            .collect::<io::Result<Vec<Partition>>>()
+    })?;
+
+    let mut result = Counts::default();
+    let mut in_word = false;
+    for Partition { counter, first } in partitions {
+        let Some(first) = first else { continue };
+
+        result.merge(&counter.counts);
+        // Слово продолжается из предыдущего куска
+        if in_word && !is_space(first) {
+            result.words -= 1;
+        }
+        in_word = counter.in_word;
+    }
+
+    // Длина строки на стыках кусков неизвестна (см. count_file)
+    result.max_line_length = 0;
+    Ok(result)
+}
+
+fn count_partition(file: &str, start: u64, length: u64) -> io::Result<Partition> {
+    let mut handle = fs::File::open(file)?;
+    handle.seek(SeekFrom::Start(start))?;
+
+    let mut counter = Counter::default();
+    let mut reader = handle.take(length);
+    let mut buffer = vec![0; BUFFER_SIZE];
+    let mut first = None;
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(bytes) => {
+                first = first.or(Some(buffer[0]));
+                counter.update(&buffer[..bytes]);
             }
-        };
-        file = &args[2];
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(Partition { counter, first })
+}
+
+/// Ширина колонок как в GNU wc:
+/// по количеству цифр суммарного размера обычных файлов,
+/// но не меньше 7, если среди источников есть STDIN или другие потоки.
+/// Одна колонка для одного источника печатается без выравнивания
+fn number_width(options: &Options, files: &[&str]) -> usize {
+    if files.len() == 1 && options.columns() == 1 {
+        return 1;
+    }
+
+    let mut minimum_width = 1;
+    let mut regular_total = 0;
+    for &file in files {
+        match fs::metadata(file) {
+            Ok(metadata) if file != "-" && metadata.is_file() => regular_total += metadata.len(),
+            Ok(_) => minimum_width = 7,
+            Err(_) if file == "-" => minimum_width = 7,
+            Err(_) => (),
+        }
     }
 
-    (flag, file)
+    regular_total.to_string().len().max(minimum_width)
+}
+
+fn print_counts(counts: &Counts, options: &Options, width: usize, name: &str) {
+    let columns = [
+        (options.lines, counts.lines),
+        (options.words, counts.words),
+        (options.chars, counts.chars),
+        (options.bytes, counts.bytes),
+        (options.max_line_length, counts.max_line_length),
+    ];
+
+    let mut line: Vec<String> = columns
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, value)| format!("{value:>width$}"))
+        .collect();
+    if name != "-" {
+        line.push(name.to_string());
+    }
+
+    println!("{}", line.join(" "));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(input: &[u8]) -> Counts {
+        count_reader(input).unwrap()
+    }
+
+    #[test]
+    fn test_count_ascii() {
+        let counts = count(b"hello world\n  foo\tbar  \nlast");
+        assert_eq!(counts.lines, 2);
+        assert_eq!(counts.words, 5);
+        assert_eq!(counts.bytes, 28);
+        assert_eq!(counts.chars, 28);
+        assert_eq!(counts.max_line_length, 13);
+    }
+
+    #[test]
+    fn test_count_utf8_and_invalid_bytes() {
+        let counts = count("привет мир\n".as_bytes());
+        assert_eq!(counts.words, 2);
+        assert_eq!(counts.chars, 11);
+        assert_eq!(counts.bytes, 20);
+        assert_eq!(counts.max_line_length, 10);
+
+        // Невалидный UTF-8 не ломает подсчет
+        let counts = count(b"\xff\xfe ab\n");
+        assert_eq!((counts.lines, counts.words, counts.bytes), (1, 2, 6));
+    }
+
+    #[test]
+    fn test_count_parallel_matches_sequential() {
+        let path = env::temp_dir().join(format!("t1_parallel_{}.txt", process::id()));
+        let content = "один два\n  три\tчетыре пять\n\nшесть семь восемь девять\n".repeat(7);
+        fs::write(&path, &content).unwrap();
+        let file = path.to_str().unwrap();
+
+        let mut expected = count(content.as_bytes());
+        expected.max_line_length = 0;
+        for threads in (1..=64).chain([content.len(), content.len() + 1]) {
+            let counts = count_parallel(file, content.len() as u64, threads).unwrap();
+            assert_eq!(counts, expected, "threads: {threads}");
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// Сравнение последовательного и параллельного подсчета на файле ~512MB.
+    /// cargo test --release --bin t1 -- --ignored --nocapture bench
+    #[test]
+    #[ignore]
+    fn bench_count_parallel() {
+        let path = env::temp_dir().join(format!("t1_bench_{}.txt", process::id()));
+        let line = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Привет, мир!\n";
+        let content = line.repeat(512 * 1024 * 1024 / line.len());
+        fs::write(&path, &content).unwrap();
+        drop(content);
+        let file = path.to_str().unwrap();
+        let size = fs::metadata(&path).unwrap().len();
+
+        let start = std::time::Instant::now();
+        let mut expected = count_reader(fs::File::open(&path).unwrap()).unwrap();
+        println!("sequential: {:?}", start.elapsed());
+        expected.max_line_length = 0;
+
+        let cpus = thread::available_parallelism().map_or(4, |n| n.get());
+        for threads in [2, 4, cpus] {
+            let start = std::time::Instant::now();
+            let counts = count_parallel(file, size, threads).unwrap();
+            println!("{threads:>2} threads: {:?}", start.elapsed());
+            assert_eq!(counts, expected);
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_counter_chunk_boundaries() {
+        let input = "один два\nтри  четыре\n".as_bytes();
+        let expected = count(input);
+        for split in 0..input.len() {
+            let mut counter = Counter::default();
+            counter.update(&input[..split]);
+            counter.update(&input[split..]);
+            assert_eq!(counter.finish(), expected);
+        }
+    }
 }
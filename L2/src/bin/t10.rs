@@ -1,10 +1,13 @@
 // L2.10
 // https://linux.die.net/man/1/telnet
+// https://www.rfc-editor.org/rfc/rfc854 (Telnet), rfc1073 (NAWS), rfc1091 (TERMINAL-TYPE)
 // Протестировано с помощью https://hub.docker.com/r/istio/tcp-echo-server
-// Завершение работы: Ctrl-D (unix) / Ctrl-Z (windows)
+// Завершение работы: Ctrl-] или конец ввода (Ctrl-D в неинтерактивном режиме)
 
 // Сервер: docker run -d -p 23:9000 istio/tcp-echo-server
 // Клиент: cargo run --bin t10 -- --timeout 30 localhost 23
+// Проверка: cargo run --bin t10 -- --script smoke.txt --log session.log localhost 23
+// TLS: cargo run --bin t10 -- --tls --insecure localhost 992
 
 /*
 
@@ -15,24 +18,89 @@ Arguments:
   [PORT]  Порт подключения [default: 23]
 
 Options:
-      --timeout <TIMEOUT>  Время ожидания подключения (в секундах) [default: 10]
+      --timeout <TIMEOUT>  Время ожидания подключения, ответа сервера после конца ввода и строки expect (в секундах) [default: 10]
+      --tls                Подключение по TLS
+  -k, --insecure           Не проверять сертификат сервера (для тестовых серверов)
+      --line-mode          Построчный режим: строка редактируется локально и отправляется по Enter (по умолчанию - посимвольный режим)
+      --script <SCRIPT>    Сценарий сеанса: строки "expect <текст>" и "send <текст>" (вместо ввода с клавиатуры)
+      --log <LOG>          Запись сеанса в файл
   -h, --help               Print help
 
 */
 
+/* Пример сценария (smoke.txt): tcp-echo-server отвечает на строку "hello <строка>"
+
+# комментарий
+send world
+expect hello world
+send 42
+expect hello 42
+
+*/
+
+/* Пример вывода
+
+Connected to localhost:23.
+Escape character is '^]'.
+hello world
+hello world
+^]
+Connection closed.
+
+*/
+
 use clap::Parser;
-use core::str;
-use std::io;
-use std::io::Read;
-use std::io::Write;
-use std::net::TcpStream;
-use std::net::ToSocketAddrs;
-use std::process::exit;
-use std::thread;
-use std::time;
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::File,
+    io::{self, IsTerminal, Read, Write},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    sync::mpsc,
+    time::Instant,
+};
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{
+        self,
+        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        crypto::CryptoProvider,
+        pki_types::{CertificateDer, ServerName, UnixTime},
+        ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    },
+    TlsConnector,
+};
+use tokio_util::either::Either;
 
 const BUFFER_SIZE: usize = 1024;
 
+/// Ctrl-] - выход из клиента
+const ESCAPE: u8 = 0x1d;
+
+/// Команды Telnet (RFC 854)
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+/// Опции Telnet
+const ECHO: u8 = 1;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+const TERMINAL_TYPE: u8 = 24;
+const NAWS: u8 = 31;
+
+/// Подкоманды TERMINAL-TYPE
+const TERMINAL_TYPE_IS: u8 = 0;
+const TERMINAL_TYPE_SEND: u8 = 1;
+
 #[derive(Parser)]
 struct Args {
     /// IP или доменное имя
@@ -42,90 +110,1095 @@ struct Args {
     #[clap(default_value_t = 23)]
     port: u16,
 
-    /// Время ожидания подключения (в секундах)
+    /// Время ожидания подключения, ответа сервера после конца ввода и строки expect (в секундах)
     #[clap(long, default_value_t = 10)]
     timeout: u64,
+
+    /// Подключение по TLS
+    #[clap(long)]
+    tls: bool,
+
+    /// Не проверять сертификат сервера (для тестовых серверов)
+    #[clap(short = 'k', long, requires = "tls")]
+    insecure: bool,
+
+    /// Построчный режим: строка редактируется локально и отправляется по Enter
+    /// (по умолчанию - посимвольный режим)
+    #[clap(long)]
+    line_mode: bool,
+
+    /// Сценарий сеанса: строки "expect <текст>" и "send <текст>" (вместо ввода с клавиатуры)
+    #[clap(long)]
+    script: Option<String>,
+
+    /// Запись сеанса в файл
+    #[clap(long)]
+    log: Option<String>,
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Обработка аргументов
-    let args = Args::parse();
-    let socket = format!("{}:{}", &args.host, &args.port)
-        .to_socket_addrs()? // Умеет обрабатывать доменные имена
-        .next()
-        .expect("No valid IP addresses were found");
-    let timeout = time::Duration::from_secs(args.timeout);
-
-    // Настройка подключения к хосту
-    let stream = TcpStream::connect_timeout(&socket, timeout)?;
-    stream.set_read_timeout(Some(timeout))?;
-    stream.set_write_timeout(Some(timeout))?;
-
-    // Поток вывода с сервера
-    let mut stream_output = stream.try_clone()?;
-    let thread_output = thread::spawn(move || {
-        let mut buffer = [0; BUFFER_SIZE];
-        loop {
-            match stream_output.read(&mut buffer) {
-                Ok(0) => {
-                    eprintln!("Connection closed by the server!");
-                    break;
+/// Состояние разбора потока от сервера
+#[derive(Debug, PartialEq)]
+enum ParseState {
+    Data,
+    /// После CR: "CR NUL" означает просто CR
+    Cr,
+    /// После IAC
+    Command,
+    /// После IAC WILL/WONT/DO/DONT - ждем опцию
+    Negotiation(u8),
+    /// Внутри IAC SB ... IAC SE
+    Subnegotiation(Vec<u8>),
+    /// IAC внутри подпереговоров
+    SubnegotiationCommand(Vec<u8>),
+}
+
+/// Данные от сервера, очищенные от команд, и ответ сервера на команды
+#[derive(Debug, Default, PartialEq)]
+struct Received {
+    data: Vec<u8>,
+    reply: Vec<u8>,
+}
+
+/// Протокол Telnet на стороне клиента: разбор команд и переговоры об опциях.
+/// Клиент сам переговоры не начинает (сервер может быть не Telnet), только отвечает
+struct Telnet {
+    state: ParseState,
+    /// Опции, включенные у клиента (мы ответили WILL)
+    local: HashSet<u8>,
+    /// Опции, включенные у сервера (мы ответили DO)
+    remote: HashSet<u8>,
+    terminal_type: String,
+    /// Размер окна терминала (ширина, высота)
+    window: (u16, u16),
+}
+
+impl Telnet {
+    fn new(terminal_type: &str, window: (u16, u16)) -> Self {
+        Self {
+            state: ParseState::Data,
+            local: HashSet::new(),
+            remote: HashSet::new(),
+            terminal_type: terminal_type.to_string(),
+            window,
+        }
+    }
+
+    /// Разбор очередной части потока (команды могут быть разбиты между частями)
+    fn receive(&mut self, input: &[u8]) -> Received {
+        let mut received = Received::default();
+        for &byte in input {
+            self.state = match std::mem::replace(&mut self.state, ParseState::Data) {
+                ParseState::Data | ParseState::Cr if byte == IAC => ParseState::Command,
+                ParseState::Cr if byte == 0 => ParseState::Data,
+                ParseState::Data | ParseState::Cr => {
+                    received.data.push(byte);
+                    match byte {
+                        b'\r' => ParseState::Cr,
+                        _ => ParseState::Data,
+                    }
                 }
-                Ok(bytes) => {
-                    println!("{}", String::from_utf8_lossy(&buffer[..bytes]));
-                    continue;
+                ParseState::Command => match byte {
+                    IAC => {
+                        received.data.push(IAC);
+                        ParseState::Data
+                    }
+                    WILL | WONT | DO | DONT => ParseState::Negotiation(byte),
+                    SB => ParseState::Subnegotiation(vec![]),
+                    // NOP, GA, AYT и т.п. клиенту не нужны
+                    _ => ParseState::Data,
+                },
+                ParseState::Negotiation(command) => {
+                    self.negotiate(command, byte, &mut received.reply);
+                    ParseState::Data
                 }
-                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
-                    eprintln!("Connection closed because of timeout!");
-                    break;
+                ParseState::Subnegotiation(data) if byte == IAC => {
+                    ParseState::SubnegotiationCommand(data)
                 }
-                Err(e) => {
-                    eprintln!("Undefined error: {}", e);
-                    break;
+                ParseState::Subnegotiation(mut data) => {
+                    data.push(byte);
+                    ParseState::Subnegotiation(data)
+                }
+                ParseState::SubnegotiationCommand(mut data) => match byte {
+                    SE => {
+                        self.subnegotiate(&data, &mut received.reply);
+                        ParseState::Data
+                    }
+                    // IAC IAC - байт 255 внутри подпереговоров
+                    _ => {
+                        data.push(byte);
+                        ParseState::Subnegotiation(data)
+                    }
+                },
+            };
+        }
+        received
+    }
+
+    /// Ответ на WILL/WONT/DO/DONT. Отвечаем только при смене состояния опции,
+    /// чтобы не зациклиться с сервером, повторяющим запросы (RFC 854)
+    fn negotiate(&mut self, command: u8, option: u8, reply: &mut Vec<u8>) {
+        match command {
+            // Сервер предлагает включить опцию у себя
+            WILL if !matches!(option, ECHO | SUPPRESS_GO_AHEAD) => {
+                reply.extend([IAC, DONT, option])
+            }
+            WILL if !self.remote.contains(&option) => {
+                self.remote.insert(option);
+                reply.extend([IAC, DO, option]);
+            }
+            WONT if self.remote.contains(&option) => {
+                self.remote.remove(&option);
+                reply.extend([IAC, DONT, option]);
+            }
+            // Сервер просит включить опцию у клиента
+            DO if !matches!(option, TERMINAL_TYPE | NAWS) => reply.extend([IAC, WONT, option]),
+            DO if !self.local.contains(&option) => {
+                self.local.insert(option);
+                reply.extend([IAC, WILL, option]);
+                if option == NAWS {
+                    reply.extend(self.window_size());
                 }
             }
+            DONT if self.local.contains(&option) => {
+                self.local.remove(&option);
+                reply.extend([IAC, WONT, option]);
+            }
+            // Состояние опции не меняется
+            _ => {}
         }
-    });
+    }
+
+    /// Ответ на IAC SB <опция> ... IAC SE
+    fn subnegotiate(&self, data: &[u8], reply: &mut Vec<u8>) {
+        if data == [TERMINAL_TYPE, TERMINAL_TYPE_SEND] && self.local.contains(&TERMINAL_TYPE) {
+            reply.extend([IAC, SB, TERMINAL_TYPE, TERMINAL_TYPE_IS]);
+            reply.extend(escape(self.terminal_type.as_bytes()));
+            reply.extend([IAC, SE]);
+        }
+    }
 
-    // Поток ввода на сервер
-    let mut stream_input = stream.try_clone()?;
-    let thread_input = thread::spawn(move || {
+    /// Изменение размера окна: сообщение серверу, если включен NAWS
+    fn resize(&mut self, window: (u16, u16)) -> Vec<u8> {
+        if window == self.window {
+            return vec![];
+        }
+        self.window = window;
+        match self.local.contains(&NAWS) {
+            true => self.window_size(),
+            false => vec![],
+        }
+    }
+
+    /// IAC SB NAWS <ширина> <высота> IAC SE
+    fn window_size(&self) -> Vec<u8> {
+        let (width, height) = self.window;
+        let mut size = width.to_be_bytes().to_vec();
+        size.extend(height.to_be_bytes());
+
+        let mut message = vec![IAC, SB, NAWS];
+        message.extend(escape(&size));
+        message.extend([IAC, SE]);
+        message
+    }
+
+    /// Сервер сам отображает введенные символы
+    fn remote_echo(&self) -> bool {
+        self.remote.contains(&ECHO)
+    }
+}
+
+/// Байт 255 в данных передается как IAC IAC
+fn escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for &byte in data {
+        if byte == IAC {
+            escaped.push(IAC);
+        }
+        escaped.push(byte);
+    }
+    escaped
+}
+
+/// Ввод пользователя для сеанса
+#[derive(Debug)]
+enum Input {
+    Keys(Vec<u8>),
+    /// Изменился размер окна терминала
+    Resize((u16, u16)),
+    Eof,
+}
+
+/// Чем закончился сеанс
+#[derive(Debug, PartialEq)]
+enum Closed {
+    /// Соединение закрыл сервер
+    Server,
+    /// Ctrl-] или конец ввода
+    Client,
+}
+
+/// Параметры сеанса
+struct Config {
+    /// Построчный режим: строку редактирует терминал, на сервер она уходит по Enter
+    line_mode: bool,
+    /// Терминал в raw-режиме (посимвольный режим): символы отправляются сразу,
+    /// а отображает их сервер (ECHO) или клиент
+    raw: bool,
+    /// Сколько ждать ответа сервера после конца ввода и строки expect в сценарии
+    timeout: Duration,
+}
+
+/// Вывод сеанса: терминал и запись сеанса (--log)
+struct Output<W> {
+    terminal: W,
+    log: Option<File>,
+}
+
+impl<W: Write> Output<W> {
+    /// Данные сервера
+    fn received(&mut self, data: &[u8]) -> io::Result<()> {
+        self.terminal.write_all(data)?;
+        self.terminal.flush()?;
+        self.log(data)
+    }
+
+    /// Отправленные данные, если их не отобразит сервер (ECHO).
+    /// display - вывести их в терминал (в построчном режиме это делает сам терминал)
+    fn sent(&mut self, data: &[u8], display: bool) -> io::Result<()> {
+        if display {
+            self.terminal.write_all(data)?;
+            self.terminal.flush()?;
+        }
+        self.log(data)
+    }
+
+    fn log(&mut self, data: &[u8]) -> io::Result<()> {
+        match &mut self.log {
+            Some(log) => log.write_all(data),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Сеанс Telnet: данные сервера выводятся в output, ввод (или сценарий) отправляется серверу.
+/// Завершается, когда сервер закрывает соединение, по Ctrl-], по концу ввода или сценария
+async fn session<S, W>(
+    stream: S,
+    telnet: &mut Telnet,
+    mut input: mpsc::UnboundedReceiver<Input>,
+    output: &mut Output<W>,
+    config: &Config,
+    mut script: Option<Script>,
+) -> io::Result<Closed>
+where
+    S: AsyncRead + AsyncWrite,
+    W: Write,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut deadline = Instant::now() + config.timeout;
+
+    // Сценарий может начинаться с send
+    if let Some(script) = &mut script {
+        let data = script.advance(&[]);
+        output.sent(&data, !telnet.remote_echo())?;
+        writer.write_all(&escape(&data)).await?;
+    }
+
+    loop {
+        if let Some(script) = &script {
+            if script.is_done() {
+                writer.shutdown().await?;
+                return Ok(Closed::Client);
+            }
+        }
+
+        tokio::select! {
+            bytes = reader.read(&mut buffer) => {
+                let bytes = bytes?;
+                if bytes == 0 {
+                    return match script.as_ref().and_then(Script::waiting) {
+                        Some((line, text)) => Err(script_error(line, text, "connection closed")),
+                        None => Ok(Closed::Server),
+                    };
+                }
+                let received = telnet.receive(&buffer[..bytes]);
+                output.received(&received.data)?;
+                writer.write_all(&received.reply).await?;
+
+                if let Some(script) = &mut script {
+                    // Каждому expect - полное время ожидания
+                    let waiting = script.waiting().map(|(line, _)| line);
+                    let data = script.advance(&received.data);
+                    if script.waiting().map(|(line, _)| line) != waiting {
+                        deadline = Instant::now() + config.timeout;
+                    }
+                    output.sent(&data, !telnet.remote_echo())?;
+                    writer.write_all(&escape(&data)).await?;
+                }
+            }
+            _ = tokio::time::sleep_until(deadline), if script.is_some() => {
+                if let Some((line, text)) = script.as_ref().and_then(Script::waiting) {
+                    return Err(script_error(line, text, "timed out"));
+                }
+            }
+            event = input.recv(), if script.is_none() => match event {
+                Some(Input::Keys(keys)) => {
+                    let (keys, escaped) = match keys.iter().position(|&key| key == ESCAPE) {
+                        Some(position) => (&keys[..position], true),
+                        None => (&keys[..], false),
+                    };
+                    let keys = translate(keys, config);
+                    if !telnet.remote_echo() {
+                        output.sent(&keys, config.raw)?;
+                    }
+                    writer.write_all(&escape(&keys)).await?;
+                    if escaped {
+                        return Ok(Closed::Client);
+                    }
+                }
+                Some(Input::Resize(window)) => writer.write_all(&telnet.resize(window)).await?,
+                Some(Input::Eof) | None => break,
+            },
+        }
+    }
+
+    // Конец ввода: сервер еще может ответить на отправленное
+    writer.shutdown().await?;
+    while let Ok(bytes) = tokio::time::timeout(config.timeout, reader.read(&mut buffer)).await {
+        let bytes = bytes?;
+        if bytes == 0 {
+            break;
+        }
+        let received = telnet.receive(&buffer[..bytes]);
+        output.received(&received.data)?;
+    }
+    Ok(Closed::Client)
+}
+
+/// Перевод строк ввода в CR LF (NVT): Enter в raw-режиме - CR, в построчном режиме - LF
+fn translate(keys: &[u8], config: &Config) -> Vec<u8> {
+    let mut translated = Vec::with_capacity(keys.len());
+    for (i, &key) in keys.iter().enumerate() {
+        match key {
+            b'\r' if config.raw => translated.extend(b"\r\n"),
+            b'\n' if config.line_mode && (i == 0 || keys[i - 1] != b'\r') => {
+                translated.extend(b"\r\n")
+            }
+            key => translated.push(key),
+        }
+    }
+    translated
+}
+
+/// Шаг сценария
+#[derive(Debug, PartialEq)]
+enum Step {
+    /// Ждать текст от сервера
+    Expect(Vec<u8>),
+    /// Отправить текст (с CR LF в конце)
+    Send(Vec<u8>),
+}
+
+/// Сценарий сеанса (--script): строки "expect <текст>" и "send <текст>",
+/// пустые строки и строки с "#" пропускаются. В тексте допустимы \r, \n, \t, \\ и \xHH
+#[derive(Debug)]
+struct Script {
+    /// Шаги с номерами строк файла
+    steps: VecDeque<(usize, Step)>,
+    /// Данные сервера после последнего совпадения
+    received: Vec<u8>,
+}
+
+impl Script {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut steps = VecDeque::new();
+        for (number, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+            let argument = unescape(argument).map_err(|err| format!("line {number}: {err}"))?;
+            let step = match command {
+                "expect" if argument.is_empty() => {
+                    return Err(format!("line {number}: empty expect"))
+                }
+                "expect" => Step::Expect(argument),
+                "send" => Step::Send([argument, b"\r\n".to_vec()].concat()),
+                _ => return Err(format!("line {number}: unknown command: {command}")),
+            };
+            steps.push_back((number, step));
+        }
+        Ok(Self {
+            steps,
+            received: vec![],
+        })
+    }
+
+    /// Учет данных сервера. Результат - что отправить: все send до следующего невыполненного expect
+    fn advance(&mut self, data: &[u8]) -> Vec<u8> {
+        self.received.extend(data);
+        let mut send = vec![];
+        while let Some((_, step)) = self.steps.front() {
+            match step {
+                Step::Send(text) => send.extend(text),
+                Step::Expect(text) => {
+                    let Some(position) = self
+                        .received
+                        .windows(text.len())
+                        .position(|window| window == text)
+                    else {
+                        break;
+                    };
+                    self.received.drain(..position + text.len());
+                }
+            }
+            self.steps.pop_front();
+        }
+        send
+    }
+
+    /// Ожидаемый текст и номер его строки
+    fn waiting(&self) -> Option<(usize, &[u8])> {
+        match self.steps.front() {
+            Some((line, Step::Expect(text))) => Some((*line, text)),
+            _ => None,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+fn script_error(line: usize, text: &[u8], reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!(
+            "script line {line}: {reason} while waiting for \"{}\"",
+            String::from_utf8_lossy(text).escape_debug()
+        ),
+    )
+}
+
+/// Текст сценария в байты: \r, \n, \t, \\, \xHH
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend(ch.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('r') => bytes.push(b'\r'),
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) if hex.len() == 2 => bytes.push(byte),
+                    _ => return Err(format!("invalid escape: \\x{hex}")),
+                }
+            }
+            Some(ch) => return Err(format!("invalid escape: \\{ch}")),
+            None => return Err("trailing backslash".to_string()),
+        }
+    }
+    Ok(bytes)
+}
+
+/// TLS поверх TCP. insecure - без проверки сертификата сервера
+async fn connect_tls(
+    stream: TcpStream,
+    host: &str,
+    insecure: bool,
+) -> io::Result<TlsStream<TcpStream>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?;
+    let config = match insecure {
+        true => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerification(provider))),
+        false => builder.with_root_certificates(RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        }),
+    }
+    .with_no_client_auth();
+
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    TlsConnector::from(Arc::new(config))
+        .connect(name, stream)
+        .await
+}
+
+/// Проверка сертификата для --insecure: принимается любой сертификат,
+/// подписи рукопожатия проверяются как обычно
+#[derive(Debug)]
+struct NoVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Чтение STDIN в отдельном потоке: блокирующее чтение не должно задерживать завершение runtime
+fn spawn_input(sender: mpsc::UnboundedSender<Input>) {
+    thread::spawn(move || {
         let mut stdin = io::stdin().lock();
         let mut buffer = [0; BUFFER_SIZE];
         loop {
-            // Чтение данных с клавиатуры
             match stdin.read(&mut buffer) {
-                Ok(0) => {
-                    eprintln!("Connection closed by the client!");
+                Ok(0) | Err(_) => {
+                    let _ = sender.send(Input::Eof);
                     break;
                 }
                 Ok(bytes) => {
-                    // Передача данных
-                    match stream_input.write(&buffer[..bytes]) {
-                        Ok(_) => continue,
-                        Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
-                            eprintln!("Connection closed because of timeout!");
-                            break;
-                        }
-                        Err(e) => {
-                            eprintln!("Undefined error: {}", e);
-                            break;
-                        }
+                    if sender.send(Input::Keys(buffer[..bytes].to_vec())).is_err() {
+                        break;
                     }
                 }
-                Err(e) => {
-                    eprintln!("Undefined error: {}", e);
-                    break;
-                }
             }
         }
     });
+}
 
-    drop(stream);
-    loop {
-        if thread_input.is_finished() || thread_output.is_finished() {
-            exit(0);
+/// Изменения размера окна (SIGWINCH)
+fn spawn_resize(sender: mpsc::UnboundedSender<Input>) {
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+        let Ok(mut resized) = signal(SignalKind::window_change()) else {
+            return;
+        };
+        while resized.recv().await.is_some() {
+            if sender.send(Input::Resize(window_size())).is_err() {
+                break;
+            }
+        }
+    });
+    #[cfg(not(unix))]
+    let _ = sender;
+}
+
+/// Размер окна терминала, (80, 24) - если STDOUT не терминал
+fn window_size() -> (u16, u16) {
+    #[cfg(unix)]
+    unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_col > 0 {
+            return (size.ws_col, size.ws_row);
+        }
+    }
+    (80, 24)
+}
+
+/// Raw-режим терминала на время сеанса: без буферизации строк, эха и обработки Ctrl-C
+/// (вывод обрабатывается как обычно). Прежний режим восстанавливается при удалении
+struct RawMode {
+    #[cfg(unix)]
+    original: libc::termios,
+}
+
+impl RawMode {
+    /// None - STDIN не терминал (или не unix)
+    fn enable() -> Option<Self> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        #[cfg(unix)]
+        unsafe {
+            let mut modes: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut modes) != 0 {
+                return None;
+            }
+            let original = modes;
+            libc::cfmakeraw(&mut modes);
+            // Обработка вывода остается: "\n" от сервера без "\r" не сдвигает строки лесенкой
+            modes.c_oflag |= libc::OPOST;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &modes) != 0 {
+                return None;
+            }
+            Some(Self { original })
+        }
+        #[cfg(not(unix))]
+        None
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original);
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Обработка аргументов
+    let args = Args::parse();
+    let timeout = Duration::from_secs(args.timeout);
+    let script = match &args.script {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+            Some(Script::parse(&text).map_err(|err| format!("{path}: {err}"))?)
+        }
+        None => None,
+    };
+    let log = match &args.log {
+        Some(path) => Some(File::create(path).map_err(|err| format!("{path}: {err}"))?),
+        None => None,
+    };
+
+    // Подключение к хосту (доменные имена разрешает tokio)
+    let address = (args.host.as_str(), args.port);
+    let stream = tokio::time::timeout(timeout, TcpStream::connect(address))
+        .await
+        .map_err(|_| "Connection timed out")??;
+    stream.set_nodelay(true)?;
+    let stream = match args.tls {
+        true => {
+            let connecting = connect_tls(stream, &args.host, args.insecure);
+            let stream = tokio::time::timeout(timeout, connecting)
+                .await
+                .map_err(|_| "TLS handshake timed out")??;
+            Either::Right(stream)
+        }
+        false => Either::Left(stream),
+    };
+    eprintln!("Connected to {}:{}.", args.host, args.port);
+    if script.is_none() {
+        eprintln!("Escape character is '^]'.");
+    }
+
+    // Ввод с клавиатуры (если нет сценария) и изменения размера окна
+    let (sender, receiver) = mpsc::unbounded_channel();
+    if script.is_none() {
+        spawn_input(sender.clone());
+    }
+    spawn_resize(sender);
+
+    let terminal_type = std::env::var("TERM").unwrap_or_else(|_| "UNKNOWN".to_string());
+    let mut telnet = Telnet::new(&terminal_type, window_size());
+    let raw = match args.line_mode || script.is_some() {
+        true => None,
+        false => RawMode::enable(),
+    };
+    let config = Config {
+        line_mode: args.line_mode,
+        raw: raw.is_some(),
+        timeout,
+    };
+    let mut output = Output {
+        terminal: io::stdout(),
+        log,
+    };
+    let closed = session(stream, &mut telnet, receiver, &mut output, &config, script).await;
+    drop(raw);
+
+    match closed {
+        Ok(Closed::Server) => eprintln!("\nConnection closed by foreign host."),
+        Ok(Closed::Client) => eprintln!("\nConnection closed."),
+        Err(err) => {
+            eprintln!("\nt10: {err}");
+            std::process::exit(1);
         }
     }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_negotiation() {
+        let mut telnet = Telnet::new("xterm", (100, 40));
+
+        // Поддерживаемые опции включаются, остальные отклоняются
+        let received = telnet.receive(&[IAC, WILL, ECHO, IAC, DO, TERMINAL_TYPE, IAC, DO, 5]);
+        assert_eq!(
+            received.reply,
+            [IAC, DO, ECHO, IAC, WILL, TERMINAL_TYPE, IAC, WONT, 5]
+        );
+        assert!(telnet.remote_echo());
+        let received = telnet.receive(&[IAC, WILL, 42]);
+        assert_eq!(received.reply, [IAC, DONT, 42]);
+
+        // Повторный запрос уже включенной опции - без ответа
+        assert!(telnet.receive(&[IAC, WILL, ECHO]).reply.is_empty());
+
+        // Выключение - с подтверждением, один раз
+        assert_eq!(telnet.receive(&[IAC, WONT, ECHO]).reply, [IAC, DONT, ECHO]);
+        assert!(telnet.receive(&[IAC, WONT, ECHO]).reply.is_empty());
+        assert!(!telnet.remote_echo());
+        assert_eq!(
+            telnet.receive(&[IAC, DONT, TERMINAL_TYPE]).reply,
+            [IAC, WONT, TERMINAL_TYPE]
+        );
+        assert!(telnet.receive(&[IAC, DONT, NAWS]).reply.is_empty());
+    }
+
+    #[test]
+    fn test_data() {
+        let mut telnet = Telnet::new("xterm", (80, 24));
+        let received = telnet.receive(b"a\r\0b\r\nc\xff\xffd\xff\xf1e");
+        assert_eq!(received.data, b"a\rb\r\nc\xffde");
+        assert!(received.reply.is_empty());
+
+        // Команда разбита между частями потока
+        assert_eq!(telnet.receive(&[b'x', IAC]).data, b"x");
+        let received = telnet.receive(&[WILL]);
+        assert_eq!(received, Received::default());
+        let received = telnet.receive(&[SUPPRESS_GO_AHEAD, b'y']);
+        assert_eq!(received.data, b"y");
+        assert_eq!(received.reply, [IAC, DO, SUPPRESS_GO_AHEAD]);
+    }
+
+    #[test]
+    fn test_terminal_type() {
+        let mut telnet = Telnet::new("xterm", (80, 24));
+        let request = [IAC, SB, TERMINAL_TYPE, TERMINAL_TYPE_SEND, IAC, SE];
+
+        // Без DO TERMINAL-TYPE не отвечаем
+        assert!(telnet.receive(&request).reply.is_empty());
+        telnet.receive(&[IAC, DO, TERMINAL_TYPE]);
+        let mut expected = vec![IAC, SB, TERMINAL_TYPE, TERMINAL_TYPE_IS];
+        expected.extend(b"xterm");
+        expected.extend([IAC, SE]);
+        assert_eq!(telnet.receive(&request).reply, expected);
+    }
+
+    #[test]
+    fn test_naws() {
+        let mut telnet = Telnet::new("xterm", (80, 24));
+        assert!(telnet.resize((100, 30)).is_empty());
+
+        let received = telnet.receive(&[IAC, DO, NAWS]);
+        assert_eq!(
+            received.reply,
+            [IAC, WILL, NAWS, IAC, SB, NAWS, 0, 100, 0, 30, IAC, SE]
+        );
+        // 255 в размере удваивается
+        assert_eq!(
+            telnet.resize((255, 256)),
+            [IAC, SB, NAWS, 0, IAC, IAC, 1, 0, IAC, SE]
+        );
+        assert!(telnet.resize((255, 256)).is_empty());
+    }
+
+    /// Сеанс с локальным сервером: сервер включает эхо, просит NAWS и отвечает на ввод
+    #[tokio::test]
+    async fn test_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket
+                .write_all(&[IAC, WILL, ECHO, IAC, DO, NAWS, b'>', b' '])
+                .await
+                .unwrap();
+            let mut received = vec![];
+            let mut buffer = [0; BUFFER_SIZE];
+            while !received.ends_with(b"\r\n") {
+                let bytes = socket.read(&mut buffer).await.unwrap();
+                received.extend(&buffer[..bytes]);
+            }
+            socket.write_all(b"hi\xff\xff\r\n").await.unwrap();
+            received
+        });
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let stream = TcpStream::connect(address).await.unwrap();
+        let mut telnet = Telnet::new("xterm", (80, 24));
+        let config = Config {
+            line_mode: false,
+            raw: true,
+            timeout: Duration::from_secs(5),
+        };
+        // Ввод - после того, как сервер включил эхо
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            sender.send(Input::Keys(b"ls \xff".to_vec())).unwrap();
+            sender.send(Input::Keys(b"\r".to_vec())).unwrap();
+            sender.send(Input::Eof).unwrap();
+        });
+        let mut output = Output {
+            terminal: vec![],
+            log: None,
+        };
+        let closed = session(stream, &mut telnet, receiver, &mut output, &config, None)
+            .await
+            .unwrap();
+
+        assert_eq!(closed, Closed::Client);
+        // Эхо делает сервер - локально ввод не выводится
+        assert_eq!(output.terminal, b"> hi\xff\r\n");
+        let mut expected = vec![IAC, DO, ECHO, IAC, WILL, NAWS];
+        expected.extend([IAC, SB, NAWS, 0, 80, 0, 24, IAC, SE]);
+        expected.extend(b"ls \xff\xff\r\n");
+        assert_eq!(server.await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_session_escape() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = vec![];
+            socket.read_to_end(&mut received).await.unwrap();
+            received
+        });
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let stream = TcpStream::connect(address).await.unwrap();
+        let mut telnet = Telnet::new("xterm", (80, 24));
+        let config = Config {
+            line_mode: false,
+            raw: true,
+            timeout: Duration::from_secs(5),
+        };
+        sender
+            .send(Input::Keys(vec![b'\xff', ESCAPE, b'b']))
+            .unwrap();
+        let mut output = Output {
+            terminal: vec![],
+            log: None,
+        };
+        let closed = session(stream, &mut telnet, receiver, &mut output, &config, None)
+            .await
+            .unwrap();
+
+        // Локальное эхо (сервер не включил ECHO), после Ctrl-] ничего не отправляется
+        assert_eq!(closed, Closed::Client);
+        assert_eq!(output.terminal, b"\xff");
+        assert_eq!(server.await.unwrap(), [IAC, IAC]);
+    }
+
+    /// Эхо-сервер как istio/tcp-echo-server: на каждую строку отвечает "hello <строка>"
+    async fn echo<S: AsyncRead + AsyncWrite + Unpin>(socket: S) {
+        use tokio::io::AsyncBufReadExt;
+        let (reader, mut writer) = tokio::io::split(socket);
+        let mut lines = tokio::io::BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let line = line.trim_end_matches('\r');
+            if writer
+                .write_all(format!("hello {line}\n").as_bytes())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+
+    async fn serve_echo() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(echo(socket));
+            }
+        });
+        address
+    }
+
+    /// Сеанс со сценарием: вывод сервера в терминал, весь сеанс - в output.log
+    async fn run_script<S: AsyncRead + AsyncWrite>(
+        stream: S,
+        script: &str,
+        timeout: Duration,
+    ) -> (io::Result<Closed>, Vec<u8>, String) {
+        let log = tempfile::NamedTempFile::new().unwrap();
+        let mut output = Output {
+            terminal: vec![],
+            log: Some(log.reopen().unwrap()),
+        };
+        let config = Config {
+            line_mode: false,
+            raw: false,
+            timeout,
+        };
+        let (_sender, receiver) = mpsc::unbounded_channel();
+        let script = Script::parse(script).unwrap();
+        let mut telnet = Telnet::new("xterm", (80, 24));
+        let closed = session(
+            stream,
+            &mut telnet,
+            receiver,
+            &mut output,
+            &config,
+            Some(script),
+        )
+        .await;
+        let log = std::fs::read_to_string(log.path()).unwrap();
+        (closed, output.terminal, log)
+    }
+
+    #[test]
+    fn test_script() {
+        let mut script = Script::parse(
+            "# комментарий\n\nexpect login:\nsend user\nsend \\x1dq\n  expect $ \nsend exit\n",
+        )
+        .unwrap();
+        assert_eq!(script.waiting(), Some((3, &b"login:"[..])));
+        assert!(script.advance(b"log").is_empty());
+        assert_eq!(script.advance(b"in: "), b"user\r\n\x1dq\r\n");
+        assert_eq!(script.waiting(), Some((6, &b"$ "[..])));
+        assert_eq!(script.advance(b"welcome\r\n$ "), b"exit\r\n");
+        assert!(script.is_done());
+
+        assert_eq!(
+            Script::parse("expect a\nwait 1").unwrap_err(),
+            "line 2: unknown command: wait"
+        );
+        assert_eq!(Script::parse("expect").unwrap_err(), "line 1: empty expect");
+        assert_eq!(
+            Script::parse("send \\q").unwrap_err(),
+            "line 1: invalid escape: \\q"
+        );
+        assert_eq!(
+            unescape(r"a\t\\\x41\r\nф").unwrap(),
+            "a\t\\A\r\nф".as_bytes()
+        );
+        assert!(unescape(r"\x4").is_err());
+    }
+
+    #[test]
+    fn test_translate() {
+        let config = |line_mode, raw| Config {
+            line_mode,
+            raw,
+            timeout: Duration::ZERO,
+        };
+        assert_eq!(translate(b"ls\r", &config(false, true)), b"ls\r\n");
+        assert_eq!(translate(b"ls\n", &config(false, false)), b"ls\n");
+        assert_eq!(
+            translate(b"ls\na\r\n", &config(true, false)),
+            b"ls\r\na\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_script() {
+        let address = serve_echo().await;
+        let stream = TcpStream::connect(address).await.unwrap();
+        let script = "send world\nexpect hello world\nsend 42\nexpect hello 42\n";
+        let (closed, terminal, log) = run_script(stream, script, Duration::from_secs(5)).await;
+
+        assert_eq!(closed.unwrap(), Closed::Client);
+        // Сервер не включил ECHO - отправленное выводится локально
+        assert_eq!(terminal, b"world\r\nhello world\n42\r\nhello 42\n");
+        assert_eq!(log, "world\r\nhello world\n42\r\nhello 42\n");
+
+        // Ответ не пришел вовремя
+        let stream = TcpStream::connect(address).await.unwrap();
+        let script = "send world\nexpect bye";
+        let (closed, _, log) = run_script(stream, script, Duration::from_millis(200)).await;
+        let err = closed.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(
+            err.to_string(),
+            "script line 2: timed out while waiting for \"bye\""
+        );
+        assert_eq!(log, "world\r\nhello world\n");
+    }
+
+    #[tokio::test]
+    async fn test_session_tls() {
+        use tokio_rustls::{rustls::ServerConfig, TlsAcceptor};
+
+        // Самоподписанный сертификат для localhost
+        let certificate =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let key = tokio_rustls::rustls::pki_types::PrivateKeyDer::try_from(
+            certificate.key_pair.serialize_der(),
+        )
+        .unwrap();
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(vec![certificate.cert.der().clone()], key)
+                .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(stream) = acceptor.accept(socket).await {
+                        echo(stream).await;
+                    }
+                });
+            }
+        });
+
+        // Сертификат не подписан известным центром сертификации
+        let stream = TcpStream::connect(address).await.unwrap();
+        let err = connect_tls(stream, "localhost", false).await.unwrap_err();
+        assert!(err.to_string().contains("UnknownIssuer"), "{err}");
+
+        let stream = TcpStream::connect(address).await.unwrap();
+        let stream = connect_tls(stream, "localhost", true).await.unwrap();
+        let script = "send secure\nexpect hello secure";
+        let (closed, terminal, _) = run_script(stream, script, Duration::from_secs(5)).await;
+        assert_eq!(closed.unwrap(), Closed::Client);
+        assert_eq!(terminal, b"secure\r\nhello secure\n");
+    }
 }
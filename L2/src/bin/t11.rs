@@ -50,7 +50,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// Код HTTP сервера
 mod controller {
     use crate::domain::Event;
-    use crate::dto::{response, QueryDate, RequestUpdateEvent};
+    use crate::dto::{response, QueryDate, QueryRange, RequestUpdateEvent};
     use crate::repository::CalendarRepository;
     use axum::extract::{Query, Request};
     use axum::middleware::{self, Next};
@@ -77,6 +77,8 @@ mod controller {
             .route("/events_for_day", get(events_for_day))
             .route("/events_for_week", get(events_for_week))
             .route("/events_for_month", get(events_for_month))
+            .route("/agenda", get(agenda))
+            .route("/freebusy", get(freebusy))
             .with_state(state)
             .layer(middleware::from_fn(logger))
     }
@@ -169,14 +171,47 @@ mod controller {
         let events = state.find_events(&start, &end).await;
         return (StatusCode::OK, Json(json!( { "success": events } )));
     }
+
+    /// События за период, сгруппированные по дням
+    async fn agenda(
+        State(state): State<Arc<dyn CalendarRepository>>,
+        Query(query): Query<QueryRange>,
+    ) -> impl IntoResponse {
+        let (start, end) = match query.create_range() {
+            Ok(range) => range,
+            Err(e) => return (StatusCode::BAD_REQUEST, response(Err(e))),
+        };
+
+        let agenda = state.agenda(&start, &end).await;
+        (StatusCode::OK, Json(json!( { "success": agenda } )))
+    }
+
+    /// Занятые интервалы пользователей за период
+    async fn freebusy(
+        State(state): State<Arc<dyn CalendarRepository>>,
+        Query(query): Query<QueryRange>,
+    ) -> impl IntoResponse {
+        let (start, end) = match query.create_range() {
+            Ok(range) => range,
+            Err(e) => return (StatusCode::BAD_REQUEST, response(Err(e))),
+        };
+        let users = match query.create_users() {
+            Ok(users) => users,
+            Err(e) => return (StatusCode::BAD_REQUEST, response(Err(e))),
+        };
+
+        let busy = state.busy(&start, &end, &users).await;
+        (StatusCode::OK, Json(json!( { "success": busy } )))
+    }
 }
 
 //////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// Работа с хранилищем данных.
 /// Прослойка между кодом HTTP сервера и бизнес логикой
 mod repository {
-    use crate::domain::Event;
+    use crate::domain::{Event, Interval};
     use chrono::NaiveDate;
+    use std::collections::BTreeMap;
     use uuid::Uuid;
 
     /// Универсальный интерфейс для работы контроллеров
@@ -186,13 +221,25 @@ mod repository {
         async fn update_event(&self, id: &Uuid, other: &Event) -> Result<(), String>;
         async fn delete_event(&self, id: &Uuid) -> Result<(), String>;
         async fn find_events(&self, start: &NaiveDate, end: &NaiveDate) -> Vec<Event>;
+        async fn agenda(
+            &self,
+            start: &NaiveDate,
+            end: &NaiveDate,
+        ) -> BTreeMap<NaiveDate, Vec<Event>>;
+        async fn busy(
+            &self,
+            start: &NaiveDate,
+            end: &NaiveDate,
+            users: &[String],
+        ) -> BTreeMap<String, Vec<Interval>>;
     }
 
     /// Реализация взаимодействия с хранилищем через Arc + RwLock
     pub mod default {
         use super::CalendarRepository;
-        use crate::domain::{Calendar, Event};
+        use crate::domain::{Calendar, Event, Interval};
         use chrono::NaiveDate;
+        use std::collections::BTreeMap;
         use std::sync::Arc;
         use tokio::sync::RwLock;
         use uuid::Uuid;
@@ -220,6 +267,27 @@ mod repository {
             async fn find_events(&self, start: &NaiveDate, end: &NaiveDate) -> Vec<Event> {
                 self.read().await.find_events(start, end)
             }
+
+            async fn agenda(
+                &self,
+                start: &NaiveDate,
+                end: &NaiveDate,
+            ) -> BTreeMap<NaiveDate, Vec<Event>> {
+                self.read().await.agenda(start, end)
+            }
+
+            async fn busy(
+                &self,
+                start: &NaiveDate,
+                end: &NaiveDate,
+                users: &[String],
+            ) -> BTreeMap<String, Vec<Interval>> {
+                let calendar = self.read().await;
+                users
+                    .iter()
+                    .map(|user| (user.clone(), calendar.busy(start, end, user)))
+                    .collect()
+            }
         }
     }
 }
@@ -288,6 +356,49 @@ mod dto {
         }
     }
 
+    /// Период в формате ?from=YYYY-MM-DD&to=YYYY-MM-DD[&users=a,b]
+    #[derive(Deserialize)]
+    pub struct QueryRange {
+        pub from: Option<String>,
+        pub to: Option<String>,
+        pub users: Option<String>,
+    }
+
+    impl QueryRange {
+        pub fn create_range(&self) -> Result<(NaiveDate, NaiveDate), &str> {
+            let (from, to) = match (&self.from, &self.to) {
+                (Some(from), Some(to)) => (from, to),
+                (None, _) => return Err("no from were provided"),
+                (_, None) => return Err("no to were provided"),
+            };
+
+            let from = NaiveDate::parse_from_str(from, "%Y-%m-%d").map_err(|_| "invalid date")?;
+            let to = NaiveDate::parse_from_str(to, "%Y-%m-%d").map_err(|_| "invalid date")?;
+            if from > to {
+                return Err("invalid range");
+            }
+
+            Ok((from, to))
+        }
+
+        pub fn create_users(&self) -> Result<Vec<String>, &str> {
+            let users: Vec<String> = match &self.users {
+                Some(users) => users
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|user| !user.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                None => vec![],
+            };
+
+            match users.is_empty() {
+                true => Err("no users were provided"),
+                false => Ok(users),
+            }
+        }
+    }
+
     pub fn response(message: Result<&str, &str>) -> Json<serde_json::Value> {
         match message {
             Ok(message) => Json(json!( { "success": message } )),
@@ -299,61 +410,458 @@ mod dto {
 //////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// Бизнес логика
 mod domain {
-    use chrono::NaiveDate;
+    use chrono::{Datelike, Days, Months, NaiveDate};
     use serde::{Deserialize, Serialize};
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap, HashSet};
     use uuid::Uuid;
 
-    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
     pub struct Event {
         title: String,
         description: String,
         date: NaiveDate,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        user: Option<String>,
+        /// Повторение начиная с date (до until включительно)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        repeat: Option<Repeat>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        until: Option<NaiveDate>,
+    }
+
+    /// Период повторения события
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Repeat {
+        Daily,
+        Weekly,
+        Monthly,
+    }
+
+    impl Repeat {
+        /// n-е повторение события с датой start (для месяцев - от start, чтобы 31-е не сдвигалось)
+        fn nth(&self, start: NaiveDate, n: u32) -> Option<NaiveDate> {
+            match self {
+                Repeat::Daily => start.checked_add_days(Days::new(n.into())),
+                Repeat::Weekly => start.checked_add_days(Days::new(u64::from(n) * 7)),
+                Repeat::Monthly => start.checked_add_months(Months::new(n)),
+            }
+        }
     }
 
+    impl Event {
+        /// Даты события в диапазоне [start; end] с учетом повторений
+        fn occurrences(&self, start: &NaiveDate, end: &NaiveDate) -> Vec<NaiveDate> {
+            let Some(repeat) = self.repeat else {
+                return match (start..=end).contains(&&self.date) {
+                    true => vec![self.date],
+                    false => vec![],
+                };
+            };
+            let last = self.until.map_or(*end, |until| until.min(*end));
+
+            // Повторения до start пропускаются без перебора
+            let days = (*start - self.date).num_days();
+            let months = (start.year() - self.date.year()) * 12 + start.month() as i32
+                - self.date.month() as i32
+                - 1;
+            let skip = match repeat {
+                Repeat::Daily => days,
+                Repeat::Weekly => days / 7,
+                Repeat::Monthly => months.into(),
+            };
+
+            let mut dates = vec![];
+            for n in u32::try_from(skip.max(0)).unwrap_or(u32::MAX).. {
+                match repeat.nth(self.date, n) {
+                    Some(date) if date <= last => {
+                        if date >= *start {
+                            dates.push(date);
+                        }
+                    }
+                    _ => break,
+                }
+            }
+            dates
+        }
+    }
+
+    /// Непрерывный период занятости [from; to]
+    #[derive(Serialize, Debug, Clone, PartialEq)]
+    pub struct Interval {
+        pub from: NaiveDate,
+        pub to: NaiveDate,
+    }
+
+    /// Хранилище событий.
+    /// Помимо основной таблицы по id поддерживаются два индекса:
+    /// упорядоченный по датам (для выборок по диапазону за O(log n + k))
+    /// и хеш-таблица самих событий (для проверки дубликатов за O(1)).
+    /// Повторяющиеся события хранятся отдельно и разворачиваются при выборке
     #[derive(Default)]
     pub struct Calendar {
         events: HashMap<Uuid, Event>,
+        dates: BTreeMap<NaiveDate, HashSet<Uuid>>,
+        uniques: HashMap<Event, Uuid>,
+        recurring: HashSet<Uuid>,
     }
 
     impl Calendar {
         pub fn create_event(&mut self, event: Event) -> Result<(), String> {
-            match self.events.iter().find(|(_, value)| **value == event) {
-                None => {
-                    self.events.insert(Uuid::new_v4(), event);
-                    Ok(())
-                }
-                Some(x) => Err(format!("This such event already exists with id: {}", x.0)),
+            if let Some(id) = self.uniques.get(&event) {
+                return Err(format!("This such event already exists with id: {id}"));
             }
+
+            self.insert(Uuid::new_v4(), event);
+            Ok(())
         }
 
         pub fn update_event(&mut self, id: &Uuid, other: &Event) -> Result<(), String> {
-            match self.events.get_mut(id) {
-                Some(event) => {
-                    event.title = other.title.clone();
-                    event.description = other.description.clone();
-                    event.date = other.date.clone();
+            if !self.events.contains_key(id) {
+                return Err(format!("Cannot find event with id: {id}"));
+            }
+
+            // Обновление не должно порождать дубликат другого события
+            match self.uniques.get(other) {
+                Some(existing) if existing != id => Err(format!(
+                    "This such event already exists with id: {existing}"
+                )),
+                _ => {
+                    self.remove(id);
+                    self.insert(*id, other.clone());
                     Ok(())
                 }
-                None => Err(format!("Cannot find event with id: {id}")),
             }
         }
 
         pub fn delete_event(&mut self, id: &Uuid) -> Result<(), String> {
-            match self.events.remove(id) {
+            match self.remove(id) {
                 Some(_) => Ok(()),
                 None => Err(format!("Cannot find event with id: {id}")),
             }
         }
 
+        /// События в диапазоне дат [start; end], упорядоченные по дате.
+        /// Повторяющееся событие попадает в выборку каждым повторением (со своей датой)
         pub fn find_events(&self, start: &NaiveDate, end: &NaiveDate) -> Vec<Event> {
-            let mut result = vec![];
-            for (_, event) in self.events.iter() {
-                if start <= &event.date && &event.date <= end {
-                    result.push(event.clone());
+            if start > end {
+                return vec![];
+            }
+
+            let mut events: Vec<Event> = self
+                .dates
+                .range(start..=end)
+                .flat_map(|(_, ids)| ids.iter())
+                .map(|id| self.events[id].clone())
+                .collect();
+            if !self.recurring.is_empty() {
+                for event in self.recurring.iter().map(|id| &self.events[id]) {
+                    for date in event.occurrences(start, end) {
+                        events.push(Event {
+                            date,
+                            ..event.clone()
+                        });
+                    }
+                }
+                events.sort_by_key(|event| event.date);
+            }
+            events
+        }
+
+        /// События в диапазоне дат [start; end], сгруппированные по дням.
+        /// Внутри дня события упорядочены по названию
+        pub fn agenda(
+            &self,
+            start: &NaiveDate,
+            end: &NaiveDate,
+        ) -> BTreeMap<NaiveDate, Vec<Event>> {
+            let mut agenda: BTreeMap<NaiveDate, Vec<Event>> = BTreeMap::new();
+            for event in self.find_events(start, end) {
+                agenda.entry(event.date).or_default().push(event);
+            }
+            for events in agenda.values_mut() {
+                events.sort_by(|a, b| a.title.cmp(&b.title));
+            }
+            agenda
+        }
+
+        /// Занятые пользователем периоды в диапазоне [start; end].
+        /// Подряд идущие занятые дни объединяются в один интервал
+        pub fn busy(&self, start: &NaiveDate, end: &NaiveDate, user: &str) -> Vec<Interval> {
+            let mut intervals: Vec<Interval> = vec![];
+            for event in self.find_events(start, end) {
+                if event.user.as_deref() != Some(user) {
+                    continue;
+                }
+                match intervals.last_mut() {
+                    Some(last) if event.date <= last.to => (),
+                    Some(last) if Some(event.date) == last.to.succ_opt() => last.to = event.date,
+                    _ => intervals.push(Interval {
+                        from: event.date,
+                        to: event.date,
+                    }),
+                }
+            }
+            intervals
+        }
+
+        /// Добавление события во все индексы
+        fn insert(&mut self, id: Uuid, event: Event) {
+            match event.repeat {
+                Some(_) => self.recurring.insert(id),
+                None => self.dates.entry(event.date).or_default().insert(id),
+            };
+            self.uniques.insert(event.clone(), id);
+            self.events.insert(id, event);
+        }
+
+        /// Удаление события из всех индексов
+        fn remove(&mut self, id: &Uuid) -> Option<Event> {
+            let event = self.events.remove(id)?;
+            self.uniques.remove(&event);
+            self.recurring.remove(id);
+            if let Some(ids) = self.dates.get_mut(&event.date) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.dates.remove(&event.date);
+                }
+            }
+            Some(event)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use chrono::Days;
+        use std::time::Instant;
+
+        fn event(title: &str, date: NaiveDate) -> Event {
+            Event {
+                title: title.to_string(),
+                description: String::new(),
+                date,
+                user: None,
+                repeat: None,
+                until: None,
+            }
+        }
+
+        fn user_event(title: &str, date: NaiveDate, user: &str) -> Event {
+            Event {
+                user: Some(user.to_string()),
+                ..event(title, date)
+            }
+        }
+
+        fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+            NaiveDate::from_ymd_opt(year, month, day).unwrap()
+        }
+
+        #[test]
+        fn test_find_events_in_range() {
+            let mut calendar = Calendar::default();
+            calendar.create_event(event("a", date(2024, 1, 1))).unwrap();
+            calendar.create_event(event("b", date(2024, 1, 5))).unwrap();
+            calendar.create_event(event("c", date(2024, 2, 1))).unwrap();
+
+            let found = calendar.find_events(&date(2024, 1, 1), &date(2024, 1, 31));
+            assert_eq!(found.len(), 2);
+            assert_eq!(found[0].title, "a");
+            assert_eq!(found[1].title, "b");
+            assert!(calendar
+                .find_events(&date(2024, 2, 2), &date(2024, 1, 1))
+                .is_empty());
+        }
+
+        #[test]
+        fn test_duplicates() {
+            let mut calendar = Calendar::default();
+            calendar.create_event(event("a", date(2024, 1, 1))).unwrap();
+            calendar.create_event(event("b", date(2024, 1, 1))).unwrap();
+            assert!(calendar.create_event(event("a", date(2024, 1, 1))).is_err());
+
+            // Нельзя превратить одно событие в копию другого
+            let id = calendar.uniques[&event("b", date(2024, 1, 1))];
+            assert!(calendar
+                .update_event(&id, &event("a", date(2024, 1, 1)))
+                .is_err());
+        }
+
+        #[test]
+        fn test_update_and_delete_keep_index() {
+            let mut calendar = Calendar::default();
+            calendar.create_event(event("a", date(2024, 1, 1))).unwrap();
+            let id = calendar.uniques[&event("a", date(2024, 1, 1))];
+
+            calendar.update_event(&id, &event("a", date(2024, 3, 1))).unwrap();
+            assert!(calendar
+                .find_events(&date(2024, 1, 1), &date(2024, 1, 1))
+                .is_empty());
+            assert_eq!(
+                calendar.find_events(&date(2024, 3, 1), &date(2024, 3, 1)),
+                vec![event("a", date(2024, 3, 1))]
+            );
+
+            calendar.delete_event(&id).unwrap();
+            assert!(calendar.dates.is_empty());
+            assert!(calendar.uniques.is_empty());
+            assert!(calendar.delete_event(&id).is_err());
+        }
+
+        #[test]
+        fn test_agenda_grouped_by_day() {
+            let mut calendar = Calendar::default();
+            calendar.create_event(event("b", date(2024, 1, 2))).unwrap();
+            calendar.create_event(event("a", date(2024, 1, 2))).unwrap();
+            calendar.create_event(event("c", date(2024, 1, 1))).unwrap();
+            calendar.create_event(event("d", date(2024, 1, 9))).unwrap();
+
+            let agenda = calendar.agenda(&date(2024, 1, 1), &date(2024, 1, 7));
+            let days: Vec<_> = agenda.keys().copied().collect();
+            assert_eq!(days, vec![date(2024, 1, 1), date(2024, 1, 2)]);
+            let titles: Vec<_> = agenda[&date(2024, 1, 2)].iter().map(|e| &e.title).collect();
+            assert_eq!(titles, vec!["a", "b"]);
+        }
+
+        #[test]
+        fn test_busy_intervals() {
+            let mut calendar = Calendar::default();
+            calendar
+                .create_event(user_event("a", date(2024, 1, 1), "alice"))
+                .unwrap();
+            calendar
+                .create_event(user_event("b", date(2024, 1, 1), "alice"))
+                .unwrap();
+            calendar
+                .create_event(user_event("c", date(2024, 1, 2), "alice"))
+                .unwrap();
+            calendar
+                .create_event(user_event("d", date(2024, 1, 5), "alice"))
+                .unwrap();
+            calendar
+                .create_event(user_event("e", date(2024, 1, 3), "bob"))
+                .unwrap();
+
+            assert_eq!(
+                calendar.busy(&date(2024, 1, 1), &date(2024, 1, 31), "alice"),
+                vec![
+                    Interval {
+                        from: date(2024, 1, 1),
+                        to: date(2024, 1, 2)
+                    },
+                    Interval {
+                        from: date(2024, 1, 5),
+                        to: date(2024, 1, 5)
+                    },
+                ]
+            );
+            assert!(calendar
+                .busy(&date(2024, 1, 1), &date(2024, 1, 31), "carol")
+                .is_empty());
+        }
+
+        #[test]
+        fn test_recurring_events() {
+            let mut calendar = Calendar::default();
+            let weekly = Event {
+                repeat: Some(Repeat::Weekly),
+                until: Some(date(2024, 1, 31)),
+                ..user_event("sync", date(2024, 1, 1), "alice")
+            };
+            let monthly = Event {
+                repeat: Some(Repeat::Monthly),
+                ..event("report", date(2024, 1, 31))
+            };
+            calendar.create_event(weekly.clone()).unwrap();
+            calendar.create_event(monthly).unwrap();
+            calendar
+                .create_event(user_event("a", date(2024, 1, 9), "alice"))
+                .unwrap();
+
+            // Повторения до начала диапазона и после until не попадают в выборку
+            let found = calendar.find_events(&date(2024, 1, 10), &date(2024, 3, 31));
+            let dates: Vec<_> = found.iter().map(|e| (e.title.as_str(), e.date)).collect();
+            assert_eq!(
+                dates,
+                vec![
+                    ("sync", date(2024, 1, 15)),
+                    ("sync", date(2024, 1, 22)),
+                    ("sync", date(2024, 1, 29)),
+                    ("report", date(2024, 1, 31)),
+                    ("report", date(2024, 2, 29)),
+                    ("report", date(2024, 3, 31)),
+                ]
+            );
+
+            let agenda = calendar.agenda(&date(2024, 1, 1), &date(2024, 1, 8));
+            assert_eq!(agenda[&date(2024, 1, 8)][0].title, "sync");
+            assert_eq!(
+                calendar.busy(&date(2024, 1, 1), &date(2024, 1, 10), "alice"),
+                vec![
+                    Interval {
+                        from: date(2024, 1, 1),
+                        to: date(2024, 1, 1)
+                    },
+                    Interval {
+                        from: date(2024, 1, 8),
+                        to: date(2024, 1, 9)
+                    },
+                ]
+            );
+
+            let id = calendar.uniques[&weekly];
+            calendar.delete_event(&id).unwrap();
+            assert!(!calendar.recurring.contains(&id));
+        }
+
+        /// Сравнение полного перебора и выборки по индексу на 1M событий.
+        /// cargo test --release --bin t11 -- --ignored --nocapture bench
+        #[test]
+        #[ignore]
+        fn bench_find_events_1m() {
+            const EVENTS: u64 = 1_000_000;
+            const QUERIES: u64 = 100;
+            let first = date(2000, 1, 1);
+
+            let start = Instant::now();
+            let mut calendar = Calendar::default();
+            for i in 0..EVENTS {
+                let day = first + Days::new(i % 3650);
+                calendar.create_event(event(&format!("event {i}"), day)).unwrap();
+            }
+            println!("create {EVENTS} events: {:?}", start.elapsed());
+
+            for (name, length) in [("day", 0), ("week", 7), ("month", 30)] {
+                let start = Instant::now();
+                let mut linear = 0;
+                for q in 0..QUERIES {
+                    let from = first + Days::new(q * 30);
+                    let to = from + Days::new(length);
+                    linear += calendar
+                        .events
+                        .values()
+                        .filter(|event| from <= event.date && event.date <= to)
+                        .count();
+                }
+                let linear_elapsed = start.elapsed();
+
+                let start = Instant::now();
+                let mut indexed = 0;
+                for q in 0..QUERIES {
+                    let from = first + Days::new(q * 30);
+                    let to = from + Days::new(length);
+                    indexed += calendar.find_events(&from, &to).len();
                 }
+                let indexed_elapsed = start.elapsed();
+
+                assert_eq!(linear, indexed);
+                println!(
+                    "{name:>5}: linear {:?} / indexed {:?} per query",
+                    linear_elapsed / QUERIES as u32,
+                    indexed_elapsed / QUERIES as u32
+                );
             }
-            result
         }
     }
 }
@@ -1,63 +1,381 @@
 // L2.2
+// echo -n "aaaabccddddde" | cargo run --bin t2
+// echo -n "a4bc2d5e" | cargo run --bin t2 -- -d
+// cargo run --bin t2 -- -d -s 'qwe\45'
+// cargo run --bin t2 -- big.txt -o big.rle
 
+/*
+
+Usage: t2.exe [OPTIONS] [FILE]
+
+Arguments:
+  [FILE]  Входной файл ("-" - STDIN) [default: -]
+
+Options:
+  -d, --decode           Распаковать (по умолчанию - упаковать)
+  -s, --string <STRING>  Обработать строку вместо файла
+  -o, --output <OUTPUT>  Выходной файл (по умолчанию - STDOUT)
+  -h, --help             Print help
+
+*/
+
+use clap::Parser;
+use std::{
+    fs,
+    io::{self, BufWriter, Read, Write},
+    iter, str,
+};
+
+#[derive(Parser)]
+struct Args {
+    /// Входной файл ("-" - STDIN)
+    #[clap(default_value = "-")]
+    file: String,
+
+    /// Распаковать (по умолчанию - упаковать)
+    #[clap(short, long)]
+    decode: bool,
+
+    /// Обработать строку вместо файла
+    #[clap(short, long, conflicts_with = "file")]
+    string: Option<String>,
+
+    /// Выходной файл (по умолчанию - STDOUT)
+    #[clap(short, long)]
+    output: Option<String>,
+}
+
+/// Размер буфера вывода потоковых адаптеров
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Распаковка: "a4bc2" -> "aaaabcc". "\" экранирует следующий символ
 fn unpack(input: &str) -> Result<String, &'static str> {
     let mut result = String::with_capacity(input.len() * 10);
-    let mut string_buffer = String::with_capacity(1); // Всегда один символ
-    let mut number_buffer = String::with_capacity(input.len()); // Любое кол-во подряд идущих цифр
-    let mut escaping = false;
-
-    // Обновляет результат и очищает буферы
-    fn flush(string_buffer: &mut String, number_buffer: &mut String, result: &mut String) {
-        if string_buffer.is_empty() {
-            return;
-        }
-        let mut repeat = 1;
-        if !number_buffer.is_empty() {
-            repeat = number_buffer.parse::<usize>().unwrap();
-            number_buffer.clear();
+    let mut decoder = Decoder::default();
+    for cur_char in input.chars() {
+        if let Some((ch, repeat)) = decoder.push(cur_char)? {
+            result.extend(iter::repeat_n(ch, repeat));
         }
-        result.push_str(string_buffer.repeat(repeat).as_str());
-        string_buffer.clear();
     }
+    if let Some((ch, repeat)) = decoder.finish()? {
+        result.extend(iter::repeat_n(ch, repeat));
+    }
+    Ok(result)
+}
 
+/// Упаковка: "aaaabcc" -> "a4bc2". Кратчайшая запись, которую распаковывает unpack
+fn pack(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut encoder = Encoder::default();
     for cur_char in input.chars() {
+        if let Some((ch, repeat)) = encoder.push(cur_char) {
+            encode_run(ch, repeat, &mut result);
+        }
+    }
+    if let Some((ch, repeat)) = encoder.finish() {
+        encode_run(ch, repeat, &mut result);
+    }
+    result
+}
+
+/// Состояние распаковки между символами входа
+#[derive(Default)]
+struct Decoder {
+    string_buffer: Option<char>,  // Всегда один символ
+    number_buffer: Option<usize>, // Любое кол-во подряд идущих цифр
+    escaping: bool,
+}
+
+impl Decoder {
+    /// Очередной символ входа. Результат - готовая серия (символ, кол-во повторов)
+    fn push(&mut self, cur_char: char) -> Result<Option<(char, usize)>, &'static str> {
         // Начинается escape-последовательность
-        if cur_char == '\\' && escaping == false {
-            escaping = true;
-            continue;
+        if cur_char == '\\' && !self.escaping {
+            self.escaping = true;
+            return Ok(None);
         }
 
         // Обрабатываем число как мильтипликатор
-        if cur_char.is_ascii_digit() && escaping == false {
-            if string_buffer.is_empty() {
+        if let Some(digit) = cur_char.to_digit(10).filter(|_| !self.escaping) {
+            if self.string_buffer.is_none() {
                 return Err("Invalid multiplicator: nothing to multiply");
             }
-            if number_buffer.is_empty() && cur_char == '0' {
-                return Err("Invalid multiplicator: begins with zero");
-            }
-            number_buffer.push(cur_char);
-            continue;
+            self.number_buffer = match self.number_buffer {
+                None if digit == 0 => return Err("Invalid multiplicator: begins with zero"),
+                None => Some(digit as usize),
+                Some(number) => Some(
+                    number
+                        .checked_mul(10)
+                        .and_then(|number| number.checked_add(digit as usize))
+                        .ok_or("Invalid multiplicator: too large")?,
+                ),
+            };
+            return Ok(None);
         }
 
         // Обновляем буферы
-        flush(&mut string_buffer, &mut number_buffer, &mut result);
-        string_buffer.push(cur_char);
-        escaping = false
+        let run = self.flush();
+        self.string_buffer = Some(cur_char);
+        self.escaping = false;
+        Ok(run)
     }
 
-    // Если '\' - был последним
-    if escaping {
-        return Err("Invalid escaping");
+    /// Конец входа: последняя серия
+    fn finish(mut self) -> Result<Option<(char, usize)>, &'static str> {
+        // Если '\' - был последним
+        if self.escaping {
+            return Err("Invalid escaping");
+        }
+        Ok(self.flush())
     }
 
-    // Обработка последней последовательности в буфере
-    flush(&mut string_buffer, &mut number_buffer, &mut result);
+    fn flush(&mut self) -> Option<(char, usize)> {
+        let ch = self.string_buffer.take()?;
+        Some((ch, self.number_buffer.take().unwrap_or(1)))
+    }
+}
 
-    Ok(result)
+/// Состояние упаковки: текущая серия одинаковых символов
+#[derive(Default)]
+struct Encoder {
+    run: Option<(char, usize)>,
+}
+
+impl Encoder {
+    /// Очередной символ входа. Результат - завершенная серия
+    fn push(&mut self, cur_char: char) -> Option<(char, usize)> {
+        match &mut self.run {
+            Some((ch, repeat)) if *ch == cur_char => {
+                *repeat += 1;
+                None
+            }
+            _ => self.run.replace((cur_char, 1)),
+        }
+    }
+
+    /// Конец входа: последняя серия
+    fn finish(self) -> Option<(char, usize)> {
+        self.run
+    }
+}
+
+/// Кратчайшая запись серии (в байтах): символ подряд или символ + кол-во.
+/// Цифры и '\' экранируются, при равной длине - с кол-вом ("a2", как в unpack)
+fn encode_run(ch: char, repeat: usize, output: &mut String) {
+    let escaped = ch.is_ascii_digit() || ch == '\\';
+    let token_len = ch.len_utf8() + escaped as usize;
+    let count = repeat.to_string();
+
+    let with_count = repeat > 1 && token_len + count.len() <= token_len * repeat;
+    let times = if with_count { 1 } else { repeat };
+    for _ in 0..times {
+        if escaped {
+            output.push('\\');
+        }
+        output.push(ch);
+    }
+    if with_count {
+        output.push_str(&count);
+    }
+}
+
+/// Накопление байтов до целых символов UTF-8 (символ может прийти по частям)
+#[derive(Default)]
+struct Utf8Buffer {
+    pending: Vec<u8>,
+}
+
+impl Utf8Buffer {
+    /// Новые байты -> все полученные целые символы
+    fn push(&mut self, buf: &[u8]) -> io::Result<String> {
+        self.pending.extend_from_slice(buf);
+        let valid = match str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(), // неполный символ в конце
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        };
+        let rest = self.pending.split_off(valid);
+        let text = String::from_utf8(std::mem::replace(&mut self.pending, rest));
+        Ok(text.expect("Couldn't split UTF-8"))
+    }
+
+    /// Конец входа: неполный символ - ошибка
+    fn finish(&self) -> io::Result<()> {
+        match self.pending.is_empty() {
+            true => Ok(()),
+            false => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "incomplete UTF-8 sequence",
+            )),
+        }
+    }
+}
+
+/// Потоковая упаковка: байты, записанные в адаптер, упаковываются в inner.
+/// Серия может продолжиться в следующей записи, поэтому в конце обязателен finish()
+struct Packer<W: Write> {
+    inner: W,
+    utf8: Utf8Buffer,
+    encoder: Encoder,
+    output: String,
+}
+
+impl<W: Write> Packer<W> {
+    fn new(inner: W) -> Self {
+        Packer {
+            inner,
+            utf8: Utf8Buffer::default(),
+            encoder: Encoder::default(),
+            output: String::with_capacity(BUFFER_SIZE),
+        }
+    }
+
+    /// Запись последней серии. Результат - исходный writer
+    fn finish(mut self) -> io::Result<W> {
+        self.utf8.finish()?;
+        if let Some((ch, repeat)) = std::mem::take(&mut self.encoder).finish() {
+            encode_run(ch, repeat, &mut self.output);
+        }
+        self.inner.write_all(self.output.as_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for Packer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for cur_char in self.utf8.push(buf)?.chars() {
+            if let Some((ch, repeat)) = self.encoder.push(cur_char) {
+                encode_run(ch, repeat, &mut self.output);
+            }
+        }
+        if self.output.len() >= BUFFER_SIZE {
+            self.inner.write_all(self.output.as_bytes())?;
+            self.output.clear();
+        }
+        Ok(buf.len())
+    }
+
+    /// Записывает готовые серии, текущая серия остается до finish()
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.write_all(self.output.as_bytes())?;
+        self.output.clear();
+        self.inner.flush()
+    }
+}
+
+/// Потоковая распаковка: упакованные байты, записанные в адаптер, распаковываются в inner.
+/// Длинные серии пишутся частями, в конце обязателен finish()
+struct Unpacker<W: Write> {
+    inner: W,
+    utf8: Utf8Buffer,
+    decoder: Decoder,
+    output: Vec<u8>,
+}
+
+impl<W: Write> Unpacker<W> {
+    fn new(inner: W) -> Self {
+        Unpacker {
+            inner,
+            utf8: Utf8Buffer::default(),
+            decoder: Decoder::default(),
+            output: Vec::with_capacity(BUFFER_SIZE),
+        }
+    }
+
+    /// Запись последней серии. Результат - исходный writer
+    fn finish(mut self) -> io::Result<W> {
+        self.utf8.finish()?;
+        let run = std::mem::take(&mut self.decoder)
+            .finish()
+            .map_err(invalid_data)?;
+        if let Some((ch, repeat)) = run {
+            self.write_run(ch, repeat)?;
+        }
+        self.inner.write_all(&self.output)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_run(&mut self, ch: char, repeat: usize) -> io::Result<()> {
+        let mut encoded = [0; 4];
+        let encoded = ch.encode_utf8(&mut encoded).as_bytes();
+        for _ in 0..repeat {
+            self.output.extend_from_slice(encoded);
+            if self.output.len() >= BUFFER_SIZE {
+                self.inner.write_all(&self.output)?;
+                self.output.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Unpacker<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for cur_char in self.utf8.push(buf)?.chars() {
+            if let Some((ch, repeat)) = self.decoder.push(cur_char).map_err(invalid_data)? {
+                self.write_run(ch, repeat)?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.write_all(&self.output)?;
+        self.output.clear();
+        self.inner.flush()
+    }
+}
+
+fn invalid_data(err: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Упаковка/распаковка потока: reader -> adapter -> writer
+fn run(reader: &mut impl Read, writer: impl Write, decode: bool) -> io::Result<()> {
+    if decode {
+        let mut unpacker = Unpacker::new(writer);
+        io::copy(reader, &mut unpacker)?;
+        unpacker.finish()?;
+    } else {
+        let mut packer = Packer::new(writer);
+        io::copy(reader, &mut packer)?;
+        packer.finish()?;
+    }
+    Ok(())
 }
 
 fn main() {
-    assert_eq!(unpack(r"😍10").unwrap(), r"😍😍😍😍😍😍😍😍😍😍");
+    let args = Args::parse();
+
+    if let Some(string) = &args.string {
+        match args.decode {
+            true => match unpack(string) {
+                Ok(result) => println!("{result}"),
+                Err(err) => {
+                    eprintln!("t2: {err}");
+                    std::process::exit(1);
+                }
+            },
+            false => println!("{}", pack(string)),
+        }
+        return;
+    }
+
+    let mut reader: Box<dyn Read> = match args.file.as_str() {
+        "-" => Box::new(io::stdin().lock()),
+        file => Box::new(fs::File::open(file).expect("Couldn't open input file")),
+    };
+    let writer: Box<dyn Write> = match &args.output {
+        Some(file) => Box::new(fs::File::create(file).expect("Couldn't create output file")),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    if let Err(err) = run(&mut reader, BufWriter::new(writer), args.decode) {
+        eprintln!("t2: {err}");
+        std::process::exit(1);
+    }
 }
 
 #[cfg(test)]
@@ -104,4 +422,93 @@ mod tests {
         assert_eq!(unpack(r"\"), Err("Invalid escaping"));
         assert_eq!(unpack(r"abc\"), Err("Invalid escaping"));
     }
+
+    #[test]
+    fn test_pack_shortest() {
+        assert_eq!(pack(r""), r"");
+        assert_eq!(pack(r"abcd"), r"abcd");
+        assert_eq!(pack(r"aa"), r"a2");
+        assert_eq!(pack(r"aaa"), r"a3");
+        assert_eq!(pack(r"aaaabccddddde"), r"a4bc2d5e");
+        assert_eq!(pack(r"aaaaaaaaaaaaaaaaaaaa"), r"a20");
+        assert_eq!(pack(r"東東京京京京京京京京京京京京"), r"東2京12");
+        assert_eq!(pack(r"😍😍😍😍😍😍😍😍😍😍"), r"😍10");
+
+        // Цифры и '\' экранируются: "\4\4" длиннее "\42"
+        assert_eq!(pack(r"qwe45"), r"qwe\4\5");
+        assert_eq!(pack(r"qwe44"), r"qwe\42");
+        assert_eq!(pack(r"qwe44444"), r"qwe\45");
+        assert_eq!(pack(r"qwe\\\\\"), r"qwe\\5");
+        assert_eq!(pack(r"1"), r"\1");
+    }
+
+    #[test]
+    fn test_pack_round_trip() {
+        // Псевдослучайные строки из небольшого алфавита с длинными сериями
+        let alphabet: Vec<char> = r"ab\09😍東 ".chars().collect();
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound as u64) as usize
+        };
+
+        for _ in 0..2000 {
+            let mut input = String::new();
+            for _ in 0..next(20) {
+                let ch = alphabet[next(alphabet.len())];
+                let repeat = if next(4) == 0 { next(30) + 1 } else { 1 };
+                input.extend(iter::repeat_n(ch, repeat));
+            }
+            let packed = pack(&input);
+            assert_eq!(unpack(&packed).unwrap(), input, "{packed}");
+            assert!(packed.len() <= input.len() * 2, "{packed}");
+            // Кратчайшая запись не сокращается повторной упаковкой своей распаковки
+            assert_eq!(pack(&unpack(&packed).unwrap()), packed);
+        }
+    }
+
+    #[test]
+    fn test_streaming_adapters() {
+        let input = format!(
+            "{}x{}\\12東京{}",
+            "a".repeat(100_000),
+            "😍".repeat(7),
+            "9".repeat(12)
+        );
+        let packed = pack(&input);
+
+        // Запись по одному байту: символы UTF-8 и серии разрезаны между вызовами write
+        let mut packer = Packer::new(vec![]);
+        for byte in input.as_bytes() {
+            packer.write_all(&[*byte]).unwrap();
+        }
+        assert_eq!(String::from_utf8(packer.finish().unwrap()).unwrap(), packed);
+
+        let mut unpacker = Unpacker::new(vec![]);
+        for byte in packed.as_bytes() {
+            unpacker.write_all(&[*byte]).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(unpacker.finish().unwrap()).unwrap(),
+            input
+        );
+
+        // Через Read -> Write
+        let mut output = vec![];
+        run(&mut packed.as_bytes(), &mut output, true).unwrap();
+        assert_eq!(output, input.as_bytes());
+
+        // Ошибки
+        let mut output = vec![];
+        assert!(run(&mut r"a012".as_bytes(), &mut output, true).is_err());
+        assert!(run(&mut r"abc\".as_bytes(), &mut output, true).is_err());
+        assert!(run(&mut &[0xff, b'a'][..], &mut output, false).is_err());
+        assert!(run(&mut &"😍".as_bytes()[..2], &mut output, false).is_err());
+        assert_eq!(
+            unpack(r"a99999999999999999999999"),
+            Err("Invalid multiplicator: too large")
+        );
+    }
 }
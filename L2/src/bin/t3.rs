@@ -1,42 +1,78 @@
 // L2.3
 // https://linux.die.net/man/1/sort
 // cargo run --bin t3 -- -u -H -k 2 -o test/sort_output.txt test/sort_input.txt
+// cargo run --bin t3 -- -S 64M --parallel=4 -o sorted.txt huge.txt
+// cat data.csv | cargo run --bin t3 -- -t ',' -k 2,2n -k 1,1r | head
+// cargo run --bin t3 -- -m sorted_1.txt sorted_2.txt > sorted.txt
 
 /*
 
-Usage: t3.exe [OPTIONS] <input_file>
+Usage: t3.exe [OPTIONS] [input_file]...
 
 Arguments:
-  <input_file>
+  [input_file]...                     Входные файлы ("-" или без файлов - STDIN)
 
 Options:
-  -o, --output <OUTPUT>         [default: output.txt]
-  -k <COLUMN>                   Указание колонки для сортировки [default: 1]
-  -n, --numeric-sort            Сортировать по числовому значению
-  -H, --human-numeric-sort      Сортировать по числовому значению с учетом суффиксов
-  -M, --month-sort              Сортировать по названию месяца
-  -r, --reverse                 Вывод в обратном порядке
-  -u, --unique                  Удалить дубликаты строк
-  -c, --check-sorted            Проверить, отсортированы ли данные
-  -b, --ignore-trailing-spaces  Игнорировать хвостовые пробелы
-  -h, --help                    Print help
+  -o, --output <OUTPUT>               Файл результата (по умолчанию STDOUT)
+  -k, --key <KEYDEF>                  Ключ сортировки F[.C][OPTS][,F[.C][OPTS]] (можно несколько) [default: 1]
+  -t, --field-separator <SEP>         Разделитель полей вместо пробельных символов
+  -n, --numeric-sort                  Сортировать по числовому значению
+  -H, --human-numeric-sort            Сортировать по числовому значению с учетом суффиксов
+  -M, --month-sort                    Сортировать по названию месяца
+  -f, --ignore-case                   Не различать регистр
+  -r, --reverse                       Вывод в обратном порядке
+  -s, --stable                        Не сравнивать строки целиком при равенстве ключей
+  -u, --unique                        Удалить дубликаты строк
+  -c, --check-sorted                  Проверить, отсортированы ли данные
+  -m, --merge                         Слить уже отсортированные файлы
+      --parallel <N>                  Сортировать в N потоков [default: 1]
+  -b, --ignore-trailing-spaces        Игнорировать хвостовые пробелы
+  -S, --buffer-size <SIZE>            Объем памяти под сортировку (суффиксы b, K, M, G, T; по умолчанию K) [default: 256M]
+  -T, --temporary-directory <DIR>     Директория для временных файлов
+  -h, --help                          Print help
+
+Ключи (KEYDEF):
+  F - номер поля, C - номер символа в поле (с 1)
+  OPTS - модификаторы ключа: b (без ведущих пробелов), f, h (как -H), M, n, r
+  Ключ без модификаторов наследует глобальные -f, -H, -M, -n, -r
+  Без второй позиции ключ заканчивается в конце первого поля
+  Номер поля больше количества полей указывает на последнее поле
 
 */
 
 use clap::Parser;
-use std::{cmp::Ordering, collections::HashSet, fs};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashSet},
+    fs,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    iter, mem,
+    path::Path,
+    thread,
+};
+use tempfile::{NamedTempFile, TempPath};
+
+/// Сколько серий сливается за один проход (как --batch-size в GNU sort).
+/// Столько же временных файлов открыто одновременно
+const MERGE_BATCH_SIZE: usize = 16;
 
 #[derive(Parser)]
 struct Args {
-    #[clap(id = "input_file")]
-    input: String,
+    /// Входные файлы ("-" или без файлов - STDIN)
+    #[clap(id = "input_file", default_value = "-")]
+    inputs: Vec<String>,
 
-    #[clap(short, long, default_value = "output.txt")]
-    output: String,
+    /// Файл результата (по умолчанию STDOUT)
+    #[clap(short, long)]
+    output: Option<String>,
 
-    /// Указание колонки для сортировки
-    #[clap(short = 'k', default_value = "1")]
-    column: usize,
+    /// Ключ сортировки F[.C][OPTS][,F[.C][OPTS]] (можно несколько)
+    #[clap(short = 'k', long = "key", default_value = "1", value_parser = SortKey::parse)]
+    keys: Vec<SortKey>,
+
+    /// Разделитель полей вместо пробельных символов
+    #[clap(short = 't', long = "field-separator")]
+    separator: Option<char>,
 
     /// Сортировать по числовому значению
     #[clap(short = 'n', long)]
@@ -50,10 +86,18 @@ struct Args {
     #[clap(short = 'M', long)]
     month_sort: bool,
 
+    /// Не различать регистр
+    #[clap(short = 'f', long)]
+    ignore_case: bool,
+
     /// Вывод в обратном порядке
     #[clap(short = 'r', long)]
     reverse: bool,
 
+    /// Не сравнивать строки целиком при равенстве ключей
+    #[clap(short = 's', long)]
+    stable: bool,
+
     /// Удалить дубликаты строк
     #[clap(short = 'u', long)]
     unique: bool,
@@ -62,108 +106,690 @@ struct Args {
     #[clap(short = 'c', long)]
     check_sorted: bool,
 
+    /// Слить уже отсортированные файлы
+    #[clap(short = 'm', long)]
+    merge: bool,
+
+    /// Сортировать в N потоков
+    #[clap(long, default_value_t = 1)]
+    parallel: usize,
+
     /// Игнорировать хвостовые пробелы
     #[clap(short = 'b', long)]
     ignore_trailing_spaces: bool,
+
+    /// Объем памяти под сортировку (суффиксы b, K, M, G, T; по умолчанию K)
+    #[clap(short = 'S', long, default_value = "256M", value_parser = parse_size)]
+    buffer_size: usize,
+
+    /// Директория для временных файлов
+    #[clap(short = 'T', long)]
+    temporary_directory: Option<String>,
 }
 
 fn main() {
-    // Подготовка
     let args = Args::parse();
-    let content = fs::read_to_string(&args.input).expect("Couldn't read the file");
-    let mut lines: Vec<&str> = content.lines().collect();
-
-    //---------------------------------------
-    // Предобработка
-
-    // Игнорирование пробелов в приоритете
-    if args.ignore_trailing_spaces {
-        lines = lines.iter().map(|line| line.trim_end()).collect()
+    if let Err(err) = run(&args) {
+        eprintln!("t3: {err}");
+        std::process::exit(2);
     }
+}
 
+fn run(args: &Args) -> io::Result<()> {
+    // Подготовка
+    let comparator = Comparator::new(args);
+    let inputs = args
+        .inputs
+        .iter()
+        .map(|input| read_input(input, args))
+        .collect::<io::Result<Vec<Source>>>()?;
+
+    //---------------------------------------
     // Проверка сортировки обязательно до фильтрации дубликатов
+
     if args.check_sorted {
-        if check_sorted(&lines, &args) {
-            println!("File \'{}\' sorted", args.input);
+        // Проверка останавливается на первой ошибке чтения
+        let mut error = None;
+        let lines = inputs
+            .into_iter()
+            .flatten()
+            .map_while(|line| line.map_err(|err| error = Some(err)).ok());
+        let sorted = check_sorted(lines, &comparator);
+        if let Some(err) = error {
+            return Err(err);
+        }
+
+        let input = args.inputs.join(", ");
+        if sorted {
+            println!("File \'{input}\' sorted");
         } else {
-            println!("File \'{}\' NOT sorted", args.input);
+            println!("File \'{input}\' NOT sorted");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    //---------------------------------------
+    // Сортировка и результат
+
+    // Файл результата создается только после чтения всего входа,
+    // чтобы можно было сортировать файл "на месте" (-o совпадает с входом, кроме -m)
+    let mut output: Option<Box<dyn Write>> = None;
+    let mut write = |line: String| -> io::Result<()> {
+        let output = match output.as_mut() {
+            Some(output) => output,
+            None => output.insert(create_output(args)?),
+        };
+        output.write_all(line.as_bytes())?;
+        output.write_all(b"\n")
+    };
+
+    if args.merge {
+        merge_sources(inputs, args, &comparator, &mut write)?;
+    } else {
+        sort(inputs.into_iter().flatten(), args, &comparator, &mut write)?;
+    }
+
+    match output.as_mut() {
+        Some(output) => output.flush(),
+        None => create_output(args).map(|_| ()),
+    }
+}
+
+/// Источник строк: входной файл, STDIN или сброшенная на диск серия
+type Source<'a> = Box<dyn Iterator<Item = io::Result<String>> + 'a>;
+
+fn read_input<'a>(input: &str, args: &'a Args) -> io::Result<Source<'a>> {
+    let reader: Box<dyn BufRead> = match input {
+        "-" => Box::new(io::stdin().lock()),
+        path => {
+            let file = fs::File::open(path)
+                .map_err(|err| io::Error::new(err.kind(), format!("{path}: {err}")))?;
+            Box::new(BufReader::new(file))
+        }
+    };
+
+    Ok(Box::new(reader.lines().map(|line| {
+        // Игнорирование пробелов в приоритете
+        line.map(|line| match args.ignore_trailing_spaces {
+            true => line.trim_end().to_string(),
+            false => line,
+        })
+    })))
+}
+
+fn create_output(args: &Args) -> io::Result<Box<dyn Write>> {
+    Ok(match &args.output {
+        Some(path) => Box::new(BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+/// Внешняя сортировка: серии сортируются в памяти, сбрасываются на диск и сливаются.
+/// Строки результата по порядку передаются в write
+fn sort(
+    lines: impl Iterator<Item = io::Result<String>>,
+    args: &Args,
+    comparator: &Comparator,
+    write: &mut impl FnMut(String) -> io::Result<()>,
+) -> io::Result<()> {
+    match split_into_runs(lines, args, comparator)? {
+        Runs::Memory(lines) => {
+            let mut unique = UniqueFilter::default();
+            for line in lines {
+                if !args.unique || unique.accept(&line, comparator) {
+                    write(line)?;
+                }
+            }
+            Ok(())
+        }
+        Runs::Files(runs) => {
+            // Слияние пачками по MERGE_BATCH_SIZE, пока серий больше, чем можно открыть разом
+            let mut runs = runs;
+            while runs.len() > MERGE_BATCH_SIZE {
+                runs = runs
+                    .chunks(MERGE_BATCH_SIZE)
+                    .map(|batch| merge_runs(batch, args, comparator))
+                    .collect::<io::Result<_>>()?;
+            }
+            let sources = runs
+                .iter()
+                .map(|run| read_run(run))
+                .collect::<io::Result<_>>()?;
+            merge_sources(sources, args, comparator, write)
+        }
+    }
+}
+
+/// Размер буфера в формате GNU sort: "1024", "512K", "64M", "1G", "100b"
+fn parse_size(value: &str) -> Result<usize, String> {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((index, suffix)) if suffix.is_ascii_alphabetic() => {
+            let multiplier = match suffix.to_ascii_uppercase() {
+                'B' => 1,
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                'T' => 1 << 40,
+                _ => return Err(format!("unknown size suffix '{suffix}'")),
+            };
+            (&value[..index], multiplier)
         }
-        return;
+        _ => (value, 1 << 10),
+    };
+
+    match number.parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number.saturating_mul(multiplier)),
+        _ => Err(format!("invalid size '{value}'")),
     }
+}
+
+/// Отсортированные серии строк
+enum Runs {
+    /// Весь вход поместился в буфер
+    Memory(Vec<String>),
+    /// Серии, сброшенные во временные файлы (закрыты, удаляются автоматически)
+    Files(Vec<TempPath>),
+}
+
+/// Чтение входа сериями не больше args.buffer_size байт.
+/// Каждая серия сортируется в памяти; если серий больше одной - они сбрасываются на диск
+fn split_into_runs(
+    lines: impl Iterator<Item = io::Result<String>>,
+    args: &Args,
+    comparator: &Comparator,
+) -> io::Result<Runs> {
+    let mut files = vec![];
+    let mut run = vec![];
+    let mut run_size = 0;
+
+    for line in lines {
+        let line = line?;
+        run_size += line.capacity() + mem::size_of::<String>();
+        run.push(line);
 
-    // Фильтрация дубликатов
+        if run_size >= args.buffer_size {
+            files.push(spill_run(&mut run, args, comparator)?);
+            run_size = 0;
+        }
+    }
+
+    if files.is_empty() {
+        sort_run(&mut run, args, comparator);
+        return Ok(Runs::Memory(run));
+    }
+    if !run.is_empty() {
+        files.push(spill_run(&mut run, args, comparator)?);
+    }
+    Ok(Runs::Files(files))
+}
+
+fn sort_run(run: &mut Vec<String>, args: &Args, comparator: &Comparator) {
+    // Стабильная сортировка: равные строки сохраняют порядок входа
+    if args.parallel > 1 && run.len() > args.parallel {
+        sort_parallel(run, args.parallel, comparator);
+    } else {
+        run.sort_by(|a, b| comparator.compare(a, b));
+    }
+
+    // Дубликаты внутри серии можно не писать на диск
     if args.unique {
-        let mut uniques = HashSet::with_capacity(lines.len());
-        lines.retain(|line| uniques.insert(line.to_string()));
+        let mut unique = UniqueFilter::default();
+        run.retain(|line| unique.accept(line, comparator));
     }
+}
 
-    //---------------------------------------
-    // Сортировка
-
-    lines.sort_by(|a, b| {
-        let a_key = get_value_on_column(a, args.column);
-        let b_key = get_value_on_column(b, args.column);
-
-        let result = if args.human_numeric_sort {
-            comparator_human_numeric(&a_key, &b_key)
-        } else if args.month_sort {
-            comparator_month(&a_key, &b_key)
-        } else if args.numeric_sort {
-            comparator_numeric(&a_key, &b_key)
+/// Серия делится на threads последовательных кусков, куски сортируются в отдельных потоках
+/// и сливаются. При равенстве строк слияние берет строку из более раннего куска,
+/// поэтому результат совпадает с последовательной стабильной сортировкой
+fn sort_parallel(run: &mut Vec<String>, threads: usize, comparator: &Comparator) {
+    let chunk_size = run.len().div_ceil(threads);
+    let mut chunks = vec![];
+    while !run.is_empty() {
+        let tail = run.split_off(chunk_size.min(run.len()));
+        chunks.push(mem::replace(run, tail));
+    }
+
+    thread::scope(|scope| {
+        for chunk in chunks.iter_mut() {
+            scope.spawn(|| chunk.sort_by(|a, b| comparator.compare(a, b)));
+        }
+    });
+
+    let sources = chunks
+        .into_iter()
+        .map(|chunk| Box::new(chunk.into_iter().map(Ok)) as Source)
+        .collect();
+    merge(sources, comparator, &mut |line| {
+        run.push(line);
+        Ok(())
+    })
+    .expect("In-memory merge can't fail");
+}
+
+fn spill_run(run: &mut Vec<String>, args: &Args, comparator: &Comparator) -> io::Result<TempPath> {
+    sort_run(run, args, comparator);
+    write_run(args, |write| run.drain(..).try_for_each(write))
+}
+
+/// Слияние пачки серий в одну новую серию
+fn merge_runs(batch: &[TempPath], args: &Args, comparator: &Comparator) -> io::Result<TempPath> {
+    let sources = batch
+        .iter()
+        .map(|run| read_run(run))
+        .collect::<io::Result<_>>()?;
+    write_run(args, |write| {
+        merge_sources(sources, args, comparator, &mut |line| write(line))
+    })
+}
+
+/// Запись серии во временный файл (в -T или системной директории).
+/// Файл закрывается после записи, поэтому открытых файлов не больше пачки слияния
+fn write_run(
+    args: &Args,
+    lines: impl FnOnce(&mut dyn FnMut(String) -> io::Result<()>) -> io::Result<()>,
+) -> io::Result<TempPath> {
+    let file = match &args.temporary_directory {
+        Some(directory) => NamedTempFile::new_in(directory)?,
+        None => NamedTempFile::new()?,
+    };
+    let mut writer = BufWriter::new(file);
+    lines(&mut |line| {
+        writer.write_all(line.as_bytes())?;
+        writer.write_all(b"\n")
+    })?;
+
+    let file = writer.into_inner().map_err(|err| err.into_error())?;
+    Ok(file.into_temp_path())
+}
+
+/// Строка источника в куче слияния
+struct HeapEntry<'a> {
+    line: String,
+    source: usize,
+    comparator: &'a Comparator,
+}
+
+impl Ord for HeapEntry<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap - max-heap, поэтому порядок обратный.
+        // При равенстве строк первым идет более ранний источник (стабильность)
+        self.comparator
+            .compare(&other.line, &self.line)
+            .then(other.source.cmp(&self.source))
+    }
+}
+
+impl PartialOrd for HeapEntry<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeapEntry<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry<'_> {}
+
+/// k-way слияние источников через кучу: в памяти одновременно по одной строке из каждого
+fn merge(
+    mut sources: Vec<Source>,
+    comparator: &Comparator,
+    write: &mut impl FnMut(String) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut heap = BinaryHeap::with_capacity(sources.len());
+
+    for (source, lines) in sources.iter_mut().enumerate() {
+        if let Some(line) = lines.next() {
+            heap.push(HeapEntry {
+                line: line?,
+                source,
+                comparator,
+            });
+        }
+    }
+
+    while let Some(HeapEntry { line, source, .. }) = heap.pop() {
+        if let Some(next) = sources[source].next() {
+            heap.push(HeapEntry {
+                line: next?,
+                source,
+                comparator,
+            });
+        }
+        write(line)?;
+    }
+
+    Ok(())
+}
+
+/// Слияние отсортированных источников с фильтрацией дубликатов (-u)
+fn merge_sources(
+    sources: Vec<Source>,
+    args: &Args,
+    comparator: &Comparator,
+    write: &mut impl FnMut(String) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut unique = UniqueFilter::default();
+    merge(sources, comparator, &mut |line| {
+        if args.unique && !unique.accept(&line, comparator) {
+            return Ok(());
+        }
+        write(line)
+    })
+}
+
+/// Чтение серии: отрезается только '\n', который был дописан при сбросе
+fn read_run<'a>(path: &Path) -> io::Result<Source<'a>> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    Ok(Box::new(iter::from_fn(move || {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => {
+                line.pop();
+                Some(Ok(line))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    })))
+}
+
+/// Фильтр дубликатов для отсортированного потока.
+/// Одинаковые строки имеют одинаковые ключи и поэтому идут в одной группе равных по ключам строк,
+/// так что достаточно помнить только строки текущей группы
+#[derive(Default)]
+struct UniqueFilter {
+    group: Option<String>,
+    seen: HashSet<String>,
+}
+
+impl UniqueFilter {
+    fn accept(&mut self, line: &str, comparator: &Comparator) -> bool {
+        match &self.group {
+            Some(group) if comparator.compare_keys(group, line) == Ordering::Equal => (),
+            _ => {
+                self.group = Some(line.to_string());
+                self.seen.clear();
+            }
+        }
+        self.seen.insert(line.to_string())
+    }
+}
+
+/// Модификаторы сравнения ключа
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+struct KeyOptions {
+    ignore_blanks: bool,
+    ignore_case: bool,
+    human_numeric: bool,
+    month: bool,
+    numeric: bool,
+    reverse: bool,
+}
+
+impl KeyOptions {
+    /// Разбор модификаторов "bfhMnr"
+    fn parse(options: &str) -> Result<Self, String> {
+        let mut result = Self::default();
+        for option in options.chars() {
+            match option {
+                'b' => result.ignore_blanks = true,
+                'f' => result.ignore_case = true,
+                'h' => result.human_numeric = true,
+                'M' => result.month = true,
+                'n' => result.numeric = true,
+                'r' => result.reverse = true,
+                _ => return Err(format!("unknown key option '{option}'")),
+            }
+        }
+        Ok(result)
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        let (a, b) = if self.ignore_blanks {
+            (a.trim_start(), b.trim_start())
         } else {
-            a_key.cmp(&b_key)
+            (a, b)
         };
 
-        if args.reverse {
+        let result = if self.human_numeric {
+            comparator_human_numeric(a, b)
+        } else if self.month {
+            comparator_month(a, b)
+        } else if self.numeric {
+            comparator_numeric(a, b)
+        } else if self.ignore_case {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        } else {
+            a.cmp(b)
+        };
+
+        if self.reverse {
             result.reverse()
         } else {
             result
         }
-    });
-
-    //---------------------------------------
-    // Результат
+    }
+}
 
-    fs::write(&args.output, lines.join("\n")).expect("Couldn't write to the file");
+/// Ключ сортировки: от поля start_field (символа start_char)
+/// до поля end_field (символа end_char, 0 - до конца поля)
+#[derive(Clone, PartialEq, Debug)]
+struct SortKey {
+    start_field: usize,
+    start_char: usize,
+    end_field: Option<usize>,
+    end_char: usize,
+    options: Option<KeyOptions>,
 }
 
-fn get_value_on_column<'a>(line: &'a str, mut nth: usize) -> &'a str {
-    if line.len() == 0 {
-        return line;
+impl SortKey {
+    /// Разбор спецификации ключа: "2", "2,2n", "1.3,1.5", "3M,3", "2.2b,4r"
+    fn parse(spec: &str) -> Result<Self, String> {
+        let (start, end) = match spec.split_once(',') {
+            Some((start, end)) => (start, Some(end)),
+            None => (spec, None),
+        };
+
+        let (start_field, start_char, start_options) = Self::parse_position(start)?;
+        if start_char == Some(0) {
+            return Err(format!("character offset is zero in key '{spec}'"));
+        }
+
+        let (end_field, end_char, end_options) = match end {
+            Some(end) => {
+                let (field, char, options) = Self::parse_position(end)?;
+                (Some(field), char.unwrap_or(0), options)
+            }
+            None => (None, 0, ""),
+        };
+
+        let options = format!("{start_options}{end_options}");
+        Ok(SortKey {
+            start_field,
+            start_char: start_char.unwrap_or(1),
+            end_field,
+            end_char,
+            options: match options.is_empty() {
+                true => None,
+                false => Some(KeyOptions::parse(&options)?),
+            },
+        })
+    }
+
+    /// "F[.C][OPTS]" -> (F, C, OPTS)
+    fn parse_position(position: &str) -> Result<(usize, Option<usize>, &str), String> {
+        let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+
+        let field_end = digits(position);
+        let field = match position[..field_end].parse::<usize>() {
+            Ok(field) if field > 0 => field,
+            _ => return Err(format!("invalid field number in key position '{position}'")),
+        };
+
+        let rest = &position[field_end..];
+        match rest.strip_prefix('.') {
+            Some(rest) => {
+                let char_end = digits(rest);
+                let char = rest[..char_end].parse::<usize>().map_err(|_| {
+                    format!("invalid character offset in key position '{position}'")
+                })?;
+                Ok((field, Some(char), &rest[char_end..]))
+            }
+            None => Ok((field, None, rest)),
+        }
     }
 
-    let words: Vec<&str> = line.split_whitespace().collect();
-    if nth < 1 {
-        nth = 1;
+    /// Текст ключа в строке
+    fn extract<'a>(&self, line: &'a str, separator: Option<char>) -> &'a str {
+        let fields = field_spans(line, separator);
+        if fields.is_empty() {
+            return line;
+        }
+
+        // Номер поля за пределами строки указывает на последнее поле
+        let last = fields.len() - 1;
+        let start_field = (self.start_field - 1).min(last);
+        let end_field = match self.end_field {
+            Some(field) => (field - 1).clamp(start_field, last),
+            None => start_field,
+        };
+
+        let (field_start, field_end) = fields[start_field];
+        let start = char_offset(line, field_start, field_end, self.start_char - 1);
+        let (field_start, field_end) = fields[end_field];
+        let end = match self.end_char {
+            0 => field_end,
+            end_char => char_offset(line, field_start, field_end, end_char),
+        };
+
+        if start < end {
+            &line[start..end]
+        } else {
+            ""
+        }
     }
-    if nth > words.len() {
-        nth = words.len()
+}
+
+/// Границы полей строки в байтах.
+/// Без разделителя поля - это группы непробельных символов (как split_whitespace),
+/// с разделителем - все подстроки между разделителями, включая пустые
+fn field_spans(line: &str, separator: Option<char>) -> Vec<(usize, usize)> {
+    let mut spans = vec![];
+    match separator {
+        Some(separator) => {
+            let mut start = 0;
+            for (index, char) in line.char_indices() {
+                if char == separator {
+                    spans.push((start, index));
+                    start = index + char.len_utf8();
+                }
+            }
+            spans.push((start, line.len()));
+        }
+        None => {
+            let mut start = None;
+            for (index, char) in line.char_indices() {
+                match (char.is_whitespace(), start) {
+                    (true, Some(word_start)) => {
+                        spans.push((word_start, index));
+                        start = None;
+                    }
+                    (false, None) => start = Some(index),
+                    _ => (),
+                }
+            }
+            if let Some(word_start) = start {
+                spans.push((word_start, line.len()));
+            }
+        }
     }
-    words[nth - 1]
+    spans
 }
 
-fn check_sorted(lines: &[&str], args: &Args) -> bool {
-    for i in 1..lines.len() {
-        let a_key = get_value_on_column(lines[i - 1], args.column);
-        let b_key = get_value_on_column(lines[i], args.column);
+/// Байтовая позиция n-го символа поля [start; end)
+fn char_offset(line: &str, start: usize, end: usize, n: usize) -> usize {
+    line[start..end]
+        .char_indices()
+        .nth(n)
+        .map_or(end, |(offset, _)| start + offset)
+}
 
-        let result = if args.human_numeric_sort {
-            comparator_human_numeric(&a_key, &b_key)
-        } else if args.month_sort {
-            comparator_month(&a_key, &b_key)
-        } else if args.numeric_sort {
-            comparator_numeric(&a_key, &b_key)
-        } else {
-            a_key.cmp(&b_key)
+/// Составной компаратор: ключи сравниваются по очереди до первого неравенства.
+/// Если все ключи равны, строки сравниваются целиком (кроме режима -s)
+struct Comparator {
+    keys: Vec<(SortKey, KeyOptions)>,
+    separator: Option<char>,
+    stable: bool,
+    reverse: bool,
+}
+
+impl Comparator {
+    fn new(args: &Args) -> Self {
+        let global = KeyOptions {
+            ignore_blanks: false,
+            ignore_case: args.ignore_case,
+            human_numeric: args.human_numeric_sort,
+            month: args.month_sort,
+            numeric: args.numeric_sort,
+            reverse: args.reverse,
         };
 
-        if result == Ordering::Greater && !args.reverse {
-            return false;
-        } else if result == Ordering::Less && args.reverse {
-            return false;
+        Comparator {
+            // Ключ без собственных модификаторов наследует глобальные
+            keys: args
+                .keys
+                .iter()
+                .map(|key| (key.clone(), key.options.unwrap_or(global)))
+                .collect(),
+            separator: args.separator,
+            stable: args.stable,
+            reverse: args.reverse,
         }
     }
 
+    fn compare_keys(&self, a: &str, b: &str) -> Ordering {
+        for (key, options) in self.keys.iter() {
+            let a_key = key.extract(a, self.separator);
+            let b_key = key.extract(b, self.separator);
+            match options.compare(a_key, b_key) {
+                Ordering::Equal => continue,
+                result => return result,
+            }
+        }
+        Ordering::Equal
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self.compare_keys(a, b) {
+            Ordering::Equal if !self.stable => {
+                let result = a.cmp(b);
+                if self.reverse {
+                    result.reverse()
+                } else {
+                    result
+                }
+            }
+            result => result,
+        }
+    }
+}
+
+fn check_sorted(lines: impl Iterator<Item = String>, comparator: &Comparator) -> bool {
+    let mut previous: Option<String> = None;
+    for line in lines {
+        if let Some(previous) = &previous {
+            if comparator.compare(previous, &line) == Ordering::Greater {
+                return false;
+            }
+        }
+        previous = Some(line);
+    }
+
     true
 }
 
@@ -246,7 +872,7 @@ fn comparator_human_numeric(a: &str, b: &str) -> Ordering {
         (Ok(number_a), Ok(number_b)) => (number_a, number_b),
         (Ok(_), Err(_)) => return Ordering::Greater,
         (Err(_), Ok(_)) => return Ordering::Less,
-        (Err(_), Err(_)) => return a.cmp(&b),
+        (Err(_), Err(_)) => return a.cmp(b),
     };
 
     // Проверка суффикса на валидность
@@ -264,9 +890,208 @@ fn comparator_human_numeric(a: &str, b: &str) -> Ordering {
     let full_number_b = number_b * SUFFIXES[idx_suffix_b].1 as f64;
     if full_number_a < full_number_b {
         Ordering::Less
-    } else if full_number_a == number_b {
+    } else if full_number_a == full_number_b {
         Ordering::Equal
     } else {
         Ordering::Greater
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(flags: &[&str]) -> String {
+        let args = Args::parse_from(["t3"].iter().chain(flags).chain(&["test/sort_input.txt"]));
+        let content = fs::read_to_string(&args.inputs[0]).unwrap();
+        let lines = content
+            .lines()
+            .map(|line| match args.ignore_trailing_spaces {
+                true => line.trim_end().to_string(),
+                false => line.to_string(),
+            })
+            .map(Ok);
+
+        let comparator = Comparator::new(&args);
+        let mut result = vec![];
+        sort(lines, &args, &comparator, &mut |line| {
+            result.push(line);
+            Ok(())
+        })
+        .unwrap();
+        result.join("\n")
+    }
+
+    fn expected(name: &str) -> String {
+        fs::read_to_string(format!("test/sort_output_{name}.txt")).unwrap()
+    }
+
+    // Эталонные файлы получены стабильной сортировкой
+    #[test]
+    fn test_sort_outputs() {
+        assert_eq!(run(&["-s", "-u"]), expected("unique"));
+        assert_eq!(run(&["-s", "-u", "-H", "-k", "2"]), expected("human"));
+        assert_eq!(run(&["-s", "-u", "-n", "-k", "2"]), expected("numeric"));
+        assert_eq!(run(&["-s", "-u", "-M", "-k", "3"]), expected("month"));
+        assert_eq!(run(&["-s", "-u", "-k", "2,2h"]), expected("human"));
+    }
+
+    fn sorted(flags: &[&str], lines: &[&str]) -> Vec<String> {
+        let args = Args::parse_from(["t3"].iter().chain(flags).chain(&["-"]));
+        let comparator = Comparator::new(&args);
+        let mut lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        sort_run(&mut lines, &args, &comparator);
+        lines
+    }
+
+    #[test]
+    fn test_multiple_keys_with_separator() {
+        let lines = ["b,2,x", "a,10,y", "c,2,z", "a,2,w"];
+        assert_eq!(
+            sorted(&["-t", ",", "-k", "2,2n", "-k", "1,1r"], &lines),
+            vec!["c,2,z", "b,2,x", "a,2,w", "a,10,y"]
+        );
+        // Пустые поля при разделителе сохраняются
+        assert_eq!(
+            sorted(&["-t", ",", "-k", "2,2"], &["b,a,c", "a,,c"]),
+            vec!["a,,c", "b,a,c"]
+        );
+    }
+
+    #[test]
+    fn test_char_offsets_and_modifiers() {
+        let lines = ["x-2024-03", "y-2023-12", "z-2024-01"];
+        assert_eq!(
+            sorted(&["-k", "1.3,1.6n", "-k", "1.8n"], &lines),
+            vec!["y-2023-12", "z-2024-01", "x-2024-03"]
+        );
+        assert_eq!(
+            sorted(&["-t", ":", "-k", "2b"], &["a:  b", "b: a"]),
+            vec!["b: a", "a:  b"]
+        );
+    }
+
+    #[test]
+    fn test_stable_and_ignore_case() {
+        let lines = ["b 1", "B 0", "a 2"];
+        assert_eq!(sorted(&["-f"], &lines), vec!["a 2", "B 0", "b 1"]);
+        assert_eq!(sorted(&["-f", "-s"], &lines), vec!["a 2", "b 1", "B 0"]);
+        assert_eq!(sorted(&["-k", "1f"], &lines), vec!["a 2", "B 0", "b 1"]);
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let key = SortKey::parse("2.3n,4.1r").unwrap();
+        assert_eq!((key.start_field, key.start_char), (2, 3));
+        assert_eq!((key.end_field, key.end_char), (Some(4), 1));
+        let options = key.options.unwrap();
+        assert!(options.numeric && options.reverse);
+
+        assert_eq!(SortKey::parse("3").unwrap().options, None);
+        assert!(SortKey::parse("0").is_err());
+        assert!(SortKey::parse("1.0").is_err());
+        assert!(SortKey::parse("1x").is_err());
+    }
+
+    #[test]
+    fn test_external_sort_matches_memory_sort() {
+        for flags in [
+            vec!["-k", "2"],
+            vec!["-u", "-H", "-k", "2"],
+            vec!["-r", "-n", "-k", "2"],
+            vec!["-b", "-u", "-M", "-k", "3"],
+        ] {
+            let in_memory = run(&flags);
+            for size in ["1b", "40b", "100b"] {
+                let external = run(&[flags.as_slice(), &["-S", size]].concat());
+                assert_eq!(external, in_memory, "{flags:?} -S {size}");
+            }
+        }
+    }
+
+    /// Серий больше MERGE_BATCH_SIZE^2: слияние в несколько проходов
+    #[test]
+    fn test_external_sort_multiple_passes() {
+        let lines: Vec<String> = (0..MERGE_BATCH_SIZE * MERGE_BATCH_SIZE * 3)
+            .map(|i| format!("{} line{}", i * 7919 % 1000, i % 13))
+            .collect();
+        let sort_lines = |flags: &[&str]| {
+            let args = Args::parse_from(["t3"].iter().chain(flags));
+            let comparator = Comparator::new(&args);
+            let mut result = vec![];
+            let lines = lines.iter().cloned().map(Ok);
+            sort(lines, &args, &comparator, &mut |line| {
+                result.push(line);
+                Ok(())
+            })
+            .unwrap();
+            result
+        };
+
+        for flags in [
+            vec!["-n"],
+            vec!["-u", "-k", "2"],
+            vec!["-s", "-r", "-k", "2"],
+        ] {
+            let in_memory = sort_lines(&flags);
+            let external = sort_lines(&[flags.as_slice(), &["-S", "1b"]].concat());
+            assert_eq!(external, in_memory, "{flags:?}");
+        }
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        for flags in [
+            vec![],
+            vec!["-u", "-n", "-k", "2"],
+            vec!["-s", "-r", "-k", "3"],
+        ] {
+            let sequential = run(&flags);
+            for threads in ["2", "3", "16", "100"] {
+                let parallel = run(&[flags.as_slice(), &["--parallel", threads]].concat());
+                assert_eq!(parallel, sequential, "{flags:?} --parallel {threads}");
+
+                let external = [flags.as_slice(), &["--parallel", threads, "-S", "200b"]].concat();
+                assert_eq!(run(&external), sequential, "{external:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_sorted_sources() {
+        let args = Args::parse_from(["t3", "-m", "-u", "-n"]);
+        let comparator = Comparator::new(&args);
+        let source = |lines: &[&str]| -> Source {
+            let lines: Vec<io::Result<String>> =
+                lines.iter().map(|line| Ok(line.to_string())).collect();
+            Box::new(lines.into_iter())
+        };
+
+        let mut result = vec![];
+        merge_sources(
+            vec![
+                source(&["1", "5", "9"]),
+                source(&["2", "5", "10"]),
+                source(&[]),
+            ],
+            &args,
+            &comparator,
+            &mut |line| {
+                result.push(line);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(result, vec!["1", "2", "5", "9", "10"]);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("10"), Ok(10 * 1024));
+        assert_eq!(parse_size("100b"), Ok(100));
+        assert_eq!(parse_size("64M"), Ok(64 << 20));
+        assert_eq!(parse_size("1g"), Ok(1 << 30));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("10X").is_err());
+    }
+}
@@ -5,7 +5,49 @@
 // т.к. на вход подаются immutable + to_lowercase() возвращает новую строку
 // Нижний регистр используется только для поиска анаграмм
 
-use std::collections::HashMap;
+/*
+
+Usage: t4.exe [OPTIONS] [FILE]
+
+Arguments:
+  [FILE]  Словарь: слова через пробелы или переводы строк ("-" - STDIN) [default: -]
+
+Options:
+  -p, --pretty    Форматированный JSON
+      --pairwise  Прежний поиск попарным сравнением слов, O(n^2 * k) (для сравнения)
+  -h, --help      Print help
+
+*/
+
+use clap::Parser;
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    fs,
+    io::{self, Read},
+};
+
+#[derive(Parser)]
+struct Args {
+    /// Словарь: слова через пробелы или переводы строк ("-" - STDIN)
+    #[clap(default_value = "-")]
+    file: String,
+
+    /// Форматированный JSON
+    #[clap(short, long)]
+    pretty: bool,
+
+    /// Прежний поиск попарным сравнением слов, O(n^2 * k) (для сравнения)
+    #[clap(long)]
+    pairwise: bool,
+}
+
+/// Сигнатура слова: отсортированные символы в нижнем регистре.
+/// У анаграмм (без учета регистра) сигнатуры совпадают
+fn signature(word: &str) -> Vec<char> {
+    let mut chars: Vec<char> = word.to_lowercase().chars().collect();
+    chars.sort_unstable();
+    chars
+}
 
 // O(n + n)
 fn are_anagrams(s1: &str, s2: &str) -> bool {
@@ -31,7 +73,8 @@ fn are_anagrams(s1: &str, s2: &str) -> bool {
     true
 }
 
-fn find_anagrams<'a>(words: &[&'a str]) -> HashMap<&'a str, Vec<&'a str>> {
+// O(n^2 * k): прежний поиск, каждое слово сравнивается со всеми ключами
+fn find_anagrams_pairwise<'a>(words: &[&'a str]) -> HashMap<&'a str, Vec<&'a str>> {
     let mut anagrams: HashMap<&'a str, Vec<&'a str>> = HashMap::with_capacity(words.len());
 
     // Поиск
@@ -58,7 +101,7 @@ fn find_anagrams<'a>(words: &[&'a str]) -> HashMap<&'a str, Vec<&'a str>> {
     let mut to_remove = vec![];
     let mut to_sort = vec![];
     for &key in anagrams.keys() {
-        if anagrams[key].len() > 0 {
+        if !anagrams[key].is_empty() {
             to_sort.push(key);
         } else {
             to_remove.push(key);
@@ -74,52 +117,79 @@ fn find_anagrams<'a>(words: &[&'a str]) -> HashMap<&'a str, Vec<&'a str>> {
     anagrams
 }
 
-fn main() {
-    let input_arr = [
-        "пятак",
-        "пятка",
-        "тяпка",
-        "листок",
-        "слиток",
-        "столик",
-        "trash",
-        "",
-    ];
-    let groups = find_anagrams(&input_arr);
-    println!("{groups:#?}");
-
-    /* Output
-
-    {
-        "пятак": [
-            "пятка",
-            "тяпка",
-        ],
-        "листок": [
-            "слиток",
-            "столик",
-        ],
+// O(n * k log k): один проход с группировкой по сигнатуре
+fn find_anagrams<'a>(words: &[&'a str]) -> HashMap<&'a str, Vec<&'a str>> {
+    // Сигнатура -> первое встретившееся слово (ключ) и остальные слова группы
+    let mut groups: HashMap<Vec<char>, (&'a str, Vec<&'a str>)> =
+        HashMap::with_capacity(words.len());
+
+    // Поиск
+    for &word in words {
+        match groups.entry(signature(word)) {
+            Entry::Occupied(mut group) => group.get_mut().1.push(word),
+            Entry::Vacant(group) => {
+                group.insert((word, Vec::new()));
+            }
+        }
     }
 
-    */
+    // Очистка: группы из одного слова не нужны, слова отсортированы и без повторов
+    groups
+        .into_values()
+        .filter(|(_, group)| !group.is_empty())
+        .map(|(key, mut group)| {
+            group.sort_unstable();
+            group.dedup();
+            (key, group)
+        })
+        .collect()
+}
 
-    let input_arr = vec!["dzx", "zxD", "dxz", "", "czd", "xzd"];
-    let groups = find_anagrams(&input_arr);
-    println!("{groups:#?}");
+/// Группы анаграмм в JSON: {"ключ": ["слово", ...]}, ключи по порядку
+fn format_json(groups: &HashMap<&str, Vec<&str>>, pretty: bool) -> String {
+    let groups: BTreeMap<_, _> = groups.iter().collect();
+    match pretty {
+        true => serde_json::to_string_pretty(&groups),
+        false => serde_json::to_string(&groups),
+    }
+    .expect("Couldn't serialize groups")
+}
 
-    /* Output
+fn main() {
+    let args = Args::parse();
 
-    {
-        "dzx": [
-            "dxz",
-            "xzd",
-            "zxD",
-        ],
+    // Чтение словаря
+    let mut dictionary = String::new();
+    match args.file.as_str() {
+        "-" => io::stdin().read_to_string(&mut dictionary),
+        file => fs::File::open(file).and_then(|mut file| file.read_to_string(&mut dictionary)),
     }
+    .expect("Couldn't read dictionary");
+    let words: Vec<&str> = dictionary.split_whitespace().collect();
 
-    */
+    let groups = match args.pairwise {
+        true => find_anagrams_pairwise(&words),
+        false => find_anagrams(&words),
+    };
+    println!("{}", format_json(&groups, args.pretty));
 }
 
+/*
+
+$ printf "пятак пятка тяпка листок слиток столик trash" | cargo run --bin t4 -- --pretty
+{
+  "листок": [
+    "слиток",
+    "столик"
+  ],
+  "пятак": [
+    "пятка",
+    "тяпка"
+  ]
+}
+
+*/
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,4 +205,96 @@ mod tests {
         assert!(!are_anagrams("rat", "cat"));
         assert!(!are_anagrams("hello", "world"));
     }
+
+    #[test]
+    fn test_find_anagrams() {
+        let groups = find_anagrams(&[
+            "пятак",
+            "пятка",
+            "тяпка",
+            "листок",
+            "слиток",
+            "столик",
+            "trash",
+            "",
+        ]);
+        assert_eq!(
+            groups,
+            HashMap::from([
+                ("пятак", vec!["пятка", "тяпка"]),
+                ("листок", vec!["слиток", "столик"]),
+            ])
+        );
+
+        // Ключ - первое слово группы в исходном регистре, повторы убираются
+        let groups = find_anagrams(&["dzx", "zxD", "dxz", "", "czd", "xzd", "dxz"]);
+        assert_eq!(groups, HashMap::from([("dzx", vec!["dxz", "xzd", "zxD"])]));
+        assert_eq!(
+            format_json(&groups, false),
+            r#"{"dzx":["dxz","xzd","zxD"]}"#
+        );
+
+        assert_eq!(
+            find_anagrams(&["Пятак", "тяпКа"]),
+            HashMap::from([("Пятак", vec!["тяпКа"])])
+        );
+        assert!(find_anagrams(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_find_anagrams_pairwise() {
+        let words = [
+            "пятак",
+            "пятка",
+            "тяпка",
+            "листок",
+            "слиток",
+            "столик",
+            "trash",
+        ];
+        assert_eq!(find_anagrams_pairwise(&words), find_anagrams(&words));
+    }
+
+    /// Бенчмарк на словаре из файла T4_DICTIONARY (например, 100 000 русских слов, по слову на строку):
+    /// T4_DICTIONARY=russian.txt cargo test --release --bin t4 -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_find_anagrams_100k() {
+        let Ok(path) = std::env::var("T4_DICTIONARY") else {
+            println!("T4_DICTIONARY is not set, skipping");
+            return;
+        };
+        let dictionary = fs::read_to_string(path).unwrap();
+        // Без повторов: прежний поиск неверно обрабатывает повторы слов
+        let mut seen = std::collections::HashSet::new();
+        let words: Vec<&str> = dictionary
+            .split_whitespace()
+            .filter(|word| seen.insert(*word))
+            .take(100_000)
+            .collect();
+
+        let start = std::time::Instant::now();
+        let groups = find_anagrams(&words);
+        println!(
+            "find_anagrams: {} words, {} groups, {:?}",
+            words.len(),
+            groups.len(),
+            start.elapsed()
+        );
+
+        // Прежний поиск квадратичен - только на первых 10 000 словах
+        let prefix = &words[..words.len().min(10_000)];
+        let start = std::time::Instant::now();
+        let groups = find_anagrams(prefix);
+        let elapsed = start.elapsed();
+        let start = std::time::Instant::now();
+        let pairwise = find_anagrams_pairwise(prefix);
+        println!(
+            "{} words, {} groups: find_anagrams {elapsed:?}, find_anagrams_pairwise {:?}",
+            prefix.len(),
+            groups.len(),
+            start.elapsed()
+        );
+        assert_eq!(groups, pairwise);
+    }
 }
@@ -1,39 +1,95 @@
 // L2.5
 // https://linux.die.net/man/1/grep
-// cargo run --bin t5 -- -n -C 1 --regexp "\d{12}" --file test/grep_input.txt
-// cargo run --bin t5 -- -n -i -C 1 --regexp pellentesque --file test/grep_input.txt
+// cargo run --bin t5 -- -n -C 1 "\d{12}" test/grep_input.txt
+// cargo run --bin t5 -- -n -i -C 1 -e pellentesque test/grep_input.txt
+// cargo run --bin t5 -- -r -n --include "*.rs" --regexp "fn main" src
+// cat test/grep_input.txt | cargo run --bin t5 -- -o -w -e amet -e "\d+" --color=auto
 
 /*
 
-Usage: t5.exe [OPTIONS] --regexp <PATTERN> --file <FILE>
+Usage: t5.exe [OPTIONS] [PATTERN] [PATH]...
+
+Arguments:
+  [PATTERN]                    Паттерн, если не задан через -e или -f
+  [PATH]...                    Файлы и директории для поиска ("-" или без путей - STDIN, с -r - текущая директория)
 
 Options:
-  -e, --regexp <PATTERN>
-  -f, --file <FILE>
-  -B, --before <BEFORE>    печатать -N строк (до совпадения)
-  -A, --after <AFTER>      печатать +N строк (после совпадения)
-  -C, --context <CONTEXT>  (A+B) печатать ±N строк (вокруг совпадения)
-  -c, --count              напечатать количество строк
-  -i, --ignore-case        игнорировать регистр
-  -v, --invert             вместо совпадения, исключать
-  -F, --fixed              точное совпадение со строкой, не паттерн
-  -n, --line-num           напечатать номера строк
-  -h, --help               Print help
+  -e, --regexp <PATTERN>       Паттерн (можно несколько)
+  -f, --file <FILE>            Файл с паттернами, по одному на строку
+  -B, --before <BEFORE>        печатать -N строк (до совпадения)
+  -A, --after <AFTER>          печатать +N строк (после совпадения)
+  -C, --context <CONTEXT>      (A+B) печатать ±N строк (вокруг совпадения)
+  -c, --count                  напечатать количество строк
+  -i, --ignore-case            игнорировать регистр
+  -v, --invert                 вместо совпадения, исключать
+  -F, --fixed                  паттерн - это строка, а не регулярное выражение
+  -w, --word-regexp            совпадение только целым словом
+  -x, --line-regexp            совпадение только целой строкой
+  -o, --only-matching          печатать только совпавшие части строк
+  -m, --max-count <NUM>        остановиться после NUM выбранных строк
+      --color[=<WHEN>]         подсвечивать совпадения [default: never] [possible values: auto, always, never]
+  -n, --line-num               напечатать номера строк
+  -r, --recursive              искать во всех файлах директорий
+      --include <GLOB>         искать только в файлах, имя которых подходит под шаблон
+      --exclude <GLOB>         пропускать файлы, имя которых подходит под шаблон
+      --exclude-dir <GLOB>     пропускать директории, имя которых подходит под шаблон
+      --no-ignore              не учитывать .gitignore
+  -H, --with-filename          печатать имя файла для каждой строки
+      --no-filename            не печатать имя файла
+  -l, --files-with-matches     напечатать только имена файлов с совпадениями
+  -L, --files-without-match    напечатать только имена файлов без совпадений
+      --threads <THREADS>      кол-во потоков для поиска по файлам
+  -h, --help                   Print help
 
 */
 
-use std::{cmp, fs};
+use clap::{Parser, ValueEnum};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::{Regex, RegexBuilder};
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Seek, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+    thread,
+};
+
+/// Сколько байт с начала файла проверять на признак бинарного файла (NUL)
+const BINARY_CHECK_SIZE: usize = 8 * 1024;
+
+/// Размер буфера чтения
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Сколько вывода файла, ожидающего своей очереди, держать в памяти (дальше - во временном файле)
+const OUTPUT_BUFFER_SIZE: usize = 1024 * 1024;
 
-use clap::Parser;
-use regex::RegexBuilder;
+/// Имя STDIN в выводе
+const STDIN_NAME: &str = "(standard input)";
+
+/// Подсветка совпадения (как в GNU grep)
+const COLOR_MATCH: &str = "\x1b[01;31m\x1b[K";
+const COLOR_RESET: &str = "\x1b[m\x1b[K";
 
 #[derive(Parser)]
 struct Args {
-    #[clap(short = 'e', long = "regexp")]
-    pattern: String,
+    /// Паттерн, если не задан через -e или -f
+    #[clap(id = "PATTERN", required_unless_present_any = ["patterns", "pattern_file"])]
+    pattern: Option<String>,
+
+    /// Файлы и директории для поиска ("-" или без путей - STDIN, с -r - текущая директория)
+    paths: Vec<String>,
 
-    #[clap(short = 'f', long)]
-    file: String,
+    /// Паттерн (можно несколько)
+    #[clap(short = 'e', long = "regexp", value_name = "PATTERN")]
+    patterns: Vec<String>,
+
+    /// Файл с паттернами, по одному на строку
+    #[clap(short = 'f', long = "file", value_name = "FILE")]
+    pattern_file: Vec<String>,
 
     /// печатать -N строк (до совпадения)
     #[clap(short = 'B', long)]
@@ -59,68 +115,1026 @@ struct Args {
     #[clap(short = 'v', long)]
     invert: bool,
 
-    /// точное совпадение со строкой, не паттерн
+    /// паттерн - это строка, а не регулярное выражение
     #[clap(short = 'F', long)]
     fixed: bool,
 
+    /// совпадение только целым словом
+    #[clap(short = 'w', long)]
+    word_regexp: bool,
+
+    /// совпадение только целой строкой
+    #[clap(short = 'x', long)]
+    line_regexp: bool,
+
+    /// печатать только совпавшие части строк
+    #[clap(short = 'o', long)]
+    only_matching: bool,
+
+    /// остановиться после NUM выбранных строк
+    #[clap(short = 'm', long, value_name = "NUM")]
+    max_count: Option<usize>,
+
+    /// подсвечивать совпадения
+    #[clap(
+        long,
+        value_name = "WHEN",
+        value_enum,
+        default_value = "never",
+        default_missing_value = "auto",
+        num_args = 0..=1,
+        require_equals = true
+    )]
+    color: Color,
+
     /// напечатать номера строк
     #[clap(short = 'n', long)]
     line_num: bool,
+
+    /// искать во всех файлах директорий
+    #[clap(short = 'r', long)]
+    recursive: bool,
+
+    /// искать только в файлах, имя которых подходит под шаблон
+    #[clap(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// пропускать файлы, имя которых подходит под шаблон
+    #[clap(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// пропускать директории, имя которых подходит под шаблон
+    #[clap(long, value_name = "GLOB")]
+    exclude_dir: Vec<String>,
+
+    /// не учитывать .gitignore
+    #[clap(long)]
+    no_ignore: bool,
+
+    /// печатать имя файла для каждой строки
+    #[clap(short = 'H', long, conflicts_with = "no_filename")]
+    with_filename: bool,
+
+    /// не печатать имя файла
+    #[clap(long)]
+    no_filename: bool,
+
+    /// напечатать только имена файлов с совпадениями
+    #[clap(short = 'l', long, conflicts_with = "files_without_match")]
+    files_with_matches: bool,
+
+    /// напечатать только имена файлов без совпадений
+    #[clap(short = 'L', long)]
+    files_without_match: bool,
+
+    /// кол-во потоков для поиска по файлам
+    #[clap(long)]
+    threads: Option<usize>,
+
+    /// Итоговое решение о подсветке (с учетом --color=auto)
+    #[clap(skip)]
+    highlight: bool,
+}
+
+impl Args {
+    /// Предварительная обработка аргументов
+    fn prepare(mut self) -> Self {
+        // С -e или -f первый аргумент - это путь, а не паттерн
+        if !self.patterns.is_empty() || !self.pattern_file.is_empty() {
+            if let Some(path) = self.pattern.take() {
+                self.paths.insert(0, path);
+            }
+        }
+        if self.paths.is_empty() {
+            let path = if self.recursive { "." } else { "-" };
+            self.paths.push(path.to_string());
+        }
+
+        self.highlight = match self.color {
+            Color::Always => true,
+            Color::Auto => io::stdout().is_terminal(),
+            Color::Never => false,
+        };
+        self
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Color {
+    Auto,
+    Always,
+    Never,
 }
 
 fn main() {
-    // Предварительная обработка аргументов
-    let args = Args::parse();
-    let content = fs::read_to_string(&args.file).expect("Couldn't read the file");
-    let re = RegexBuilder::new(&args.pattern)
-        .case_insensitive(args.ignore_case)
-        .build()
-        .expect("Couldn't compile regular expression");
-
-    // Обработка ключей контекста (-C или -A + -B)
-    let before = args.context.unwrap_or(args.before.unwrap_or_default());
-    let after = args.context.unwrap_or(args.after.unwrap_or_default());
-
-    // Поиск
-    let lines: Vec<&str> = content.lines().collect();
-    let mut result = vec![];
-    for (index, line) in lines.iter().enumerate() {
-        let find = if args.fixed {
-            // Точное совпадение со строкой
-            if args.ignore_case {
-                line.to_lowercase() == args.pattern.to_lowercase()
-            } else {
-                line.to_string() == args.pattern
-            }
-        } else {
-            // Содержит паттерн
-            re.find(line).is_some()
+    let args = Args::parse().prepare();
+    let matcher = Matcher::new(&args);
+    let filter = FileFilter::new(&args);
+    let files = collect_files(&args, &filter);
+
+    // Имена файлов печатаются, если файлов может быть больше одного
+    let with_filename =
+        !args.no_filename && (args.with_filename || args.recursive || args.paths.len() > 1);
+
+    match search_files(&files, &args, &matcher, with_filename, &mut io::stdout()) {
+        Ok(found) => std::process::exit(if found { 0 } else { 1 }),
+        // Читатель закрыл канал (например, `| head`) - завершаемся молча
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => std::process::exit(0),
+        Err(err) => {
+            eprintln!("t5: {err}");
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Поиск совпадений в строке.
+/// Все паттерны объединяются в одно регулярное выражение через "|"
+struct Matcher {
+    regex: Option<Regex>,
+    word: bool,
+    invert: bool,
+}
+
+impl Matcher {
+    fn new(args: &Args) -> Self {
+        let mut patterns = args.patterns.clone();
+        patterns.extend(args.pattern.clone());
+        for file in args.pattern_file.iter() {
+            let content = match file.as_str() {
+                "-" => io::read_to_string(io::stdin()),
+                file => fs::read_to_string(file),
+            };
+            let content = content.expect("Couldn't read pattern file");
+            patterns.extend(content.lines().map(String::from));
+        }
+
+        // Пустой список паттернов (пустой файл) не совпадает ни с чем
+        let regex = match patterns.is_empty() {
+            true => None,
+            false => {
+                let alternation = patterns
+                    .iter()
+                    .map(|pattern| match args.fixed {
+                        true => format!("(?:{})", regex::escape(pattern)),
+                        false => format!("(?:{pattern})"),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("|");
+                // -w: совпадение (группа 1) окружено не-словом. Так при неудачной границе
+                // пробуются и другие варианты с того же места: -w 'foo|foobar' находит "foobar"
+                let pattern = match (args.line_regexp, args.word_regexp) {
+                    (true, _) => format!("^(?:{alternation})$"),
+                    (false, true) => format!(r"(?:^|\W)({alternation})(?:\W|$)"),
+                    (false, false) => alternation,
+                };
+                let regex = RegexBuilder::new(&pattern)
+                    .case_insensitive(args.ignore_case)
+                    .build()
+                    .expect("Couldn't compile regular expression");
+                Some(regex)
+            }
         };
 
+        Matcher {
+            regex,
+            word: args.word_regexp && !args.line_regexp,
+            invert: args.invert,
+        }
+    }
+
+    /// Подходит ли строка с учетом -v
+    fn is_selected(&self, line: &str) -> bool {
+        let find = self
+            .regex
+            .as_ref()
+            .is_some_and(|regex| regex.is_match(line));
+
         // XOR invert
-        if find ^ args.invert {
-            // + контекст
-            let start = cmp::max(index - before, 0);
-            let finish = cmp::min(lines.len() - 1, index + after);
-            for i in start..=finish {
-                if !result.contains(&i) {
-                    result.push(i);
+        find ^ self.invert
+    }
+
+    /// Границы всех совпадений в строке (с учетом -w), включая пустые
+    fn find_spans(&self, line: &str) -> Vec<(usize, usize)> {
+        let Some(regex) = &self.regex else {
+            return vec![];
+        };
+
+        let mut spans = vec![];
+        let mut position = 0;
+        loop {
+            // С -w совпадение - группа 1, без границ слова вокруг
+            let found = match self.word {
+                true => regex
+                    .captures_at(line, position)
+                    .and_then(|captures| captures.get(1)),
+                false => regex.find_at(line, position),
+            };
+            let Some(found) = found else { break };
+            let (start, end) = (found.start(), found.end());
+            spans.push((start, end));
+            position = end;
+            // Пустое совпадение - сдвигаемся на символ вперед
+            if position == start {
+                match line[start..].chars().next() {
+                    Some(char) => position += char.len_utf8(),
+                    None => break,
+                }
+            }
+        }
+        spans
+    }
+}
+
+/// Фильтрация файлов по --include/--exclude/--exclude-dir (по имени файла, как в GNU grep)
+#[derive(Clone)]
+struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    exclude_dir: GlobSet,
+}
+
+impl FileFilter {
+    fn new(args: &Args) -> Self {
+        let build = |globs: &[String]| -> GlobSet {
+            let mut builder = GlobSetBuilder::new();
+            for glob in globs {
+                builder.add(Glob::new(glob).expect("Couldn't compile glob"));
+            }
+            builder.build().expect("Couldn't compile glob")
+        };
+
+        FileFilter {
+            include: match args.include.is_empty() {
+                true => None,
+                false => Some(build(&args.include)),
+            },
+            exclude: build(&args.exclude),
+            exclude_dir: build(&args.exclude_dir),
+        }
+    }
+
+    fn is_file_allowed(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name() else {
+            return true;
+        };
+        if self.exclude.is_match(name) {
+            return false;
+        }
+        match &self.include {
+            Some(include) => include.is_match(name),
+            None => true,
+        }
+    }
+
+    fn is_dir_allowed(&self, path: &Path) -> bool {
+        match path.file_name() {
+            Some(name) => name != ".git" && !self.exclude_dir.is_match(name),
+            None => true,
+        }
+    }
+}
+
+/// Список файлов для поиска в детерминированном порядке:
+/// пути из аргументов по порядку, содержимое директорий - по имени
+fn collect_files(args: &Args, filter: &FileFilter) -> Vec<String> {
+    let mut files = vec![];
+    for path in args.paths.iter() {
+        if !Path::new(path).is_dir() {
+            // Явно указанные файлы ищутся всегда (как в GNU grep)
+            files.push(path.clone());
+            continue;
+        }
+        if !args.recursive {
+            eprintln!("t5: {path}: Is a directory");
+            continue;
+        }
+
+        let dir_filter = filter.clone();
+        let walker = ignore::WalkBuilder::new(path)
+            .standard_filters(false)
+            .git_ignore(!args.no_ignore)
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .filter_entry(move |entry| {
+                !entry.file_type().is_some_and(|t| t.is_dir())
+                    || dir_filter.is_dir_allowed(entry.path())
+            })
+            .build();
+
+        for entry in walker {
+            match entry {
+                Ok(entry) if entry.file_type().is_some_and(|t| t.is_file()) => {
+                    if filter.is_file_allowed(entry.path()) {
+                        files.push(entry.path().display().to_string());
+                    }
+                }
+                Ok(_) => (),
+                Err(err) => eprintln!("t5: {err}"),
+            }
+        }
+    }
+    files
+}
+
+/// Поиск по файлам.
+/// Один поток пишет результат сразу в output. Несколько потоков забирают файлы по очереди,
+/// а вывод печатается строго в порядке списка файлов: первый в очереди файл пишет
+/// сразу в output, остальные копят вывод до своей очереди (см. FileOutput).
+/// Ошибка чтения файла выводится, и поиск продолжается. Ошибка записи в output
+/// останавливает поиск во всех потоках и возвращается
+fn search_files(
+    files: &[String],
+    args: &Args,
+    matcher: &Matcher,
+    with_filename: bool,
+    output: &mut (impl Write + Send),
+) -> io::Result<bool> {
+    let threads = args
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, files.len().max(1));
+
+    let output = BufWriter::new(output);
+    let mut found = false;
+
+    if threads == 1 {
+        let mut output = WatchedOutput {
+            output,
+            failed: false,
+        };
+        for file in files {
+            match search_file(file, args, matcher, with_filename, &mut output) {
+                Ok(selected) => found |= selected.unwrap_or_default() > 0,
+                Err(err) if output.failed => return Err(err),
+                Err(err) => eprintln!("t5: {file}: {err}"),
+            }
+        }
+        output.flush()?;
+        return Ok(found);
+    }
+
+    let next = AtomicUsize::new(0);
+    let order = Order {
+        head: Mutex::new(0),
+        turn: Condvar::new(),
+        output: Mutex::new(output),
+        error: Mutex::new(None),
+        stopped: AtomicBool::new(false),
+    };
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut found = false;
+                    while !order.is_stopped() {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(file) = files.get(index) else { break };
+                        let mut output = FileOutput::new(index, &order);
+                        let result = search_file(file, args, matcher, with_filename, &mut output);
+                        let failed = output.failed;
+                        match (result, output.finish()) {
+                            // Поиск остановлен из-за ошибки вывода в другом потоке
+                            _ if order.is_stopped() => (),
+                            (_, Err(err)) => order.stop(err),
+                            (Err(err), Ok(())) if failed => order.stop(err),
+                            (Err(err), Ok(())) => eprintln!("t5: {file}: {err}"),
+                            (Ok(selected), Ok(())) => found |= selected.unwrap_or_default() > 0,
+                        }
+                        // Очередь двигается всегда, иначе следующие файлы ждали бы вечно
+                        order.next();
+                    }
+                    found
+                })
+            })
+            .collect();
+        for worker in workers {
+            found |= worker.join().expect("Search thread panicked");
+        }
+    });
+
+    if let Some(err) = order.error.into_inner().unwrap() {
+        return Err(err);
+    }
+    order.output.into_inner().unwrap().flush()?;
+    Ok(found)
+}
+
+/// Вывод, запоминающий ошибку записи: так ошибка вывода отличается от ошибки чтения файла
+struct WatchedOutput<W: Write> {
+    output: W,
+    failed: bool,
+}
+
+impl<W: Write> Write for WatchedOutput<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let result = self.output.write(data);
+        self.failed |= result.is_err();
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.output.flush();
+        self.failed |= result.is_err();
+        result
+    }
+}
+
+/// Очередь вывода при поиске в несколько потоков
+struct Order<W: Write> {
+    /// Номер файла, чей вывод сейчас печатается
+    head: Mutex<usize>,
+    turn: Condvar,
+    output: Mutex<W>,
+    /// Первая ошибка вывода
+    error: Mutex<Option<io::Error>>,
+    /// Поиск остановлен из-за ошибки вывода
+    stopped: AtomicBool,
+}
+
+impl<W: Write> Order<W> {
+    fn is_head(&self, index: usize) -> bool {
+        *self.head.lock().unwrap() == index
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Ожидание очереди файла (или остановки поиска)
+    fn wait_for(&self, index: usize) {
+        let head = self.head.lock().unwrap();
+        let waiting = |head: &mut usize| *head != index && !self.is_stopped();
+        drop(self.turn.wait_while(head, waiting).unwrap());
+    }
+
+    /// Остановка поиска во всех потоках из-за ошибки вывода
+    fn stop(&self, err: io::Error) {
+        self.error.lock().unwrap().get_or_insert(err);
+        let _head = self.head.lock().unwrap();
+        self.stopped.store(true, Ordering::SeqCst);
+        self.turn.notify_all();
+    }
+
+    /// Очередь переходит к следующему файлу
+    fn next(&self) {
+        *self.head.lock().unwrap() += 1;
+        self.turn.notify_all();
+    }
+}
+
+/// Вывод одного файла при поиске в несколько потоков.
+/// Пока файл не первый в очереди, вывод копится в памяти (до OUTPUT_BUFFER_SIZE),
+/// а сверх этого - во временном файле. Когда очередь доходит до файла, накопленное
+/// печатается и дальше вывод идет сразу в output, поэтому память не зависит от размера файлов
+struct FileOutput<'a, W: Write> {
+    index: usize,
+    order: &'a Order<W>,
+    buffer: Vec<u8>,
+    spill: Option<fs::File>,
+    direct: bool,
+    /// Была ошибка записи
+    failed: bool,
+}
+
+impl<'a, W: Write> FileOutput<'a, W> {
+    fn new(index: usize, order: &'a Order<W>) -> Self {
+        Self {
+            index,
+            order,
+            buffer: vec![],
+            spill: None,
+            direct: false,
+            failed: false,
+        }
+    }
+
+    /// Печать накопленного вывода
+    fn write_pending(&mut self) -> io::Result<()> {
+        let mut output = self.order.output.lock().unwrap();
+        if let Some(mut spill) = self.spill.take() {
+            spill.rewind()?;
+            io::copy(&mut spill, &mut *output)?;
+        }
+        output.write_all(&self.buffer)?;
+        self.buffer = vec![];
+        Ok(())
+    }
+
+    /// Ожидание очереди и печать остатка вывода (после остановки поиска - не печатается)
+    fn finish(mut self) -> io::Result<()> {
+        if !self.direct {
+            self.order.wait_for(self.index);
+            if !self.order.is_stopped() {
+                self.write_pending()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.order.is_stopped() {
+            return Err(io::Error::other("search stopped"));
+        }
+        if !self.direct && self.order.is_head(self.index) {
+            self.write_pending()?;
+            self.direct = true;
+        }
+        if self.direct {
+            return self.order.output.lock().unwrap().write(data);
+        }
+
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() >= OUTPUT_BUFFER_SIZE {
+            let spill = match &mut self.spill {
+                Some(spill) => spill,
+                None => self.spill.insert(tempfile::tempfile()?),
+            };
+            spill.write_all(&self.buffer)?;
+            self.buffer.clear();
+        }
+        Ok(data.len())
+    }
+}
+
+impl<W: Write> Write for FileOutput<'_, W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let result = self.write_data(data);
+        self.failed |= result.is_err();
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Поиск в одном файле ("-" - STDIN).
+/// Результат - кол-во выбранных строк, None - файл бинарный и пропущен
+fn search_file(
+    file: &str,
+    args: &Args,
+    matcher: &Matcher,
+    with_filename: bool,
+    output: &mut impl Write,
+) -> io::Result<Option<usize>> {
+    let (input, file): (Box<dyn Read>, &str) = match file {
+        "-" => (Box::new(io::stdin().lock()), STDIN_NAME),
+        file => (Box::new(fs::File::open(file)?), file),
+    };
+
+    let mut reader = BufReader::with_capacity(READ_BUFFER_SIZE, input);
+    let head = reader.fill_buf()?;
+    if head[..head.len().min(BINARY_CHECK_SIZE)].contains(&0) {
+        return Ok(None);
+    }
+
+    let name = with_filename.then_some(file);
+    search(reader, args, matcher, file, name, output).map(Some)
+}
+
+/// Потоковый поиск: в памяти только текущая строка и не более -B строк контекста.
+/// Результат - кол-во выбранных строк
+fn search(
+    mut reader: impl BufRead,
+    args: &Args,
+    matcher: &Matcher,
+    file: &str,
+    name: Option<&str>,
+    output: &mut impl Write,
+) -> io::Result<usize> {
+    // Обработка ключей контекста (-C или -A + -B), с -o контекст не печатается
+    let (before, after) = match args.only_matching {
+        true => (0, 0),
+        false => (
+            args.context.unwrap_or(args.before.unwrap_or_default()),
+            args.context.unwrap_or(args.after.unwrap_or_default()),
+        ),
+    };
+    let max_count = args.max_count.unwrap_or(usize::MAX);
+    let list_files = args.files_with_matches || args.files_without_match;
+    let print_lines = !list_files && !args.count;
+
+    let mut printer = LinePrinter {
+        output,
+        args,
+        matcher,
+        name,
+        separate_groups: before > 0 || after > 0,
+        last_printed: None,
+    };
+
+    // Кольцевой буфер для -B и обратный отсчет для -A
+    let mut ring: VecDeque<(usize, String)> = VecDeque::with_capacity(before);
+    let mut after_left = 0;
+
+    let mut selected = 0;
+    let mut buffer = vec![];
+    for index in 0.. {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            break;
+        }
+        // Как str::lines: без "\n" и "\r\n"
+        if buffer.last() == Some(&b'\n') {
+            buffer.pop();
+            if buffer.last() == Some(&b'\r') {
+                buffer.pop();
+            }
+        }
+        let line = String::from_utf8_lossy(&buffer);
+
+        // После -m NUM выбранных строк печатается только хвост контекста
+        let stopped = selected >= max_count;
+        if stopped && after_left == 0 {
+            break;
+        }
+
+        if !stopped && matcher.is_selected(&line) {
+            selected += 1;
+            if list_files {
+                break; // для -l/-L достаточно первого совпадения
+            }
+            if print_lines {
+                for (index, line) in ring.drain(..) {
+                    printer.print(index, &line, false)?;
                 }
+                printer.print(index, &line, true)?;
+                after_left = after;
+            }
+        } else if print_lines && after_left > 0 {
+            after_left -= 1;
+            printer.print(index, &line, false)?;
+        } else if print_lines && before > 0 {
+            if ring.len() == before {
+                ring.pop_front();
+            }
+            ring.push_back((index, line.into_owned()));
+        }
+    }
+
+    // Вывод итогов
+    if list_files {
+        if (selected == 0) != args.files_with_matches {
+            writeln!(output, "{file}")?;
+        }
+    } else if args.count {
+        match name {
+            Some(name) => writeln!(output, "{name}:{selected}")?,
+            None => writeln!(output, "{selected}")?,
+        }
+    }
+
+    Ok(selected)
+}
+
+/// Печать строк результата с префиксами и разделителями групп контекста "--"
+struct LinePrinter<'a, W: Write> {
+    output: &'a mut W,
+    args: &'a Args,
+    matcher: &'a Matcher,
+    name: Option<&'a str>,
+    separate_groups: bool,
+    last_printed: Option<usize>,
+}
+
+impl<W: Write> LinePrinter<'_, W> {
+    fn print(&mut self, index: usize, line: &str, is_selected: bool) -> io::Result<()> {
+        // Разрыв между группами контекста
+        if self.separate_groups && self.last_printed.is_some_and(|last| index > last + 1) {
+            writeln!(self.output, "--")?;
+        }
+        self.last_printed = Some(index);
+
+        // + имя файла (":" - совпадение, "-" - контекст) и номер строки
+        let mut prefix = String::new();
+        if let Some(name) = self.name {
+            let separator = if is_selected { ':' } else { '-' };
+            prefix = format!("{name}{separator}");
+        }
+        if self.args.line_num {
+            prefix += &format!("{} ", index + 1);
+        }
+
+        if !self.args.only_matching {
+            self.output.write_all(prefix.as_bytes())?;
+            match self.args.highlight {
+                true => self.write_highlighted(line, &self.matcher.find_spans(line))?,
+                false => self.output.write_all(line.as_bytes())?,
             }
+            return writeln!(self.output);
+        }
+
+        // -o: каждое непустое совпадение на отдельной строке (с -v ничего)
+        if self.args.invert {
+            return Ok(());
         }
+        for (start, end) in self.matcher.find_spans(line) {
+            if start == end {
+                continue;
+            }
+            self.output.write_all(prefix.as_bytes())?;
+            let span = [(0, end - start)];
+            match self.args.highlight {
+                true => self.write_highlighted(&line[start..end], &span)?,
+                false => self.output.write_all(&line.as_bytes()[start..end])?,
+            }
+            writeln!(self.output)?;
+        }
+        Ok(())
     }
 
-    // Вывод
-    if args.count {
-        println!("{}", result.len())
-    } else {
-        for index in result {
-            // + номера строк
-            if args.line_num {
-                print!("{} ", index + 1);
+    /// Строка с подсвеченными участками
+    fn write_highlighted(&mut self, line: &str, spans: &[(usize, usize)]) -> io::Result<()> {
+        let mut position = 0;
+        for &(start, end) in spans.iter().filter(|(start, end)| start < end) {
+            self.output.write_all(&line.as_bytes()[position..start])?;
+            self.output.write_all(COLOR_MATCH.as_bytes())?;
+            self.output.write_all(&line.as_bytes()[start..end])?;
+            self.output.write_all(COLOR_RESET.as_bytes())?;
+            position = end;
+        }
+        self.output.write_all(&line.as_bytes()[position..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(flags: &[&str]) -> Args {
+        Args::parse_from(["t5", "--regexp", "needle"].iter().chain(flags)).prepare()
+    }
+
+    /// Дерево: a.rs, b.txt, .gitignore (ignored.rs), target/c.rs, data.bin
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.rs"), "needle\n").unwrap();
+        fs::write(root.join("b.txt"), "haystack\nneedle\n").unwrap();
+        fs::write(root.join(".gitignore"), "ignored.rs\n").unwrap();
+        fs::write(root.join("ignored.rs"), "needle\n").unwrap();
+        fs::create_dir(root.join("target")).unwrap();
+        fs::write(root.join("target/c.rs"), "needle\n").unwrap();
+        fs::write(root.join("data.bin"), b"needle\0\n").unwrap();
+        dir
+    }
+
+    fn collect(dir: &tempfile::TempDir, flags: &[&str]) -> Vec<String> {
+        let root = dir.path().display().to_string();
+        let args = args(&[&["-r"], flags, &[root.as_str()]].concat());
+        let filter = FileFilter::new(&args);
+        collect_files(&args, &filter)
+            .into_iter()
+            .map(|file| file[root.len() + 1..].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_collect_files() {
+        let dir = fixture();
+        assert_eq!(
+            collect(&dir, &[]),
+            vec![".gitignore", "a.rs", "b.txt", "data.bin", "target/c.rs"]
+        );
+        assert_eq!(
+            collect(&dir, &["--include", "*.rs"]),
+            vec!["a.rs", "target/c.rs"]
+        );
+        assert_eq!(
+            collect(&dir, &["--include", "*.rs", "--exclude-dir", "target"]),
+            vec!["a.rs"]
+        );
+        assert_eq!(
+            collect(&dir, &["--exclude", "*.rs", "--exclude", ".*"]),
+            vec!["b.txt", "data.bin"]
+        );
+        assert_eq!(
+            collect(&dir, &["--include", "*.rs", "--no-ignore"]),
+            vec!["a.rs", "ignored.rs", "target/c.rs"]
+        );
+    }
+
+    #[test]
+    fn test_search_output() {
+        let dir = fixture();
+        let file = dir.path().join("b.txt").display().to_string();
+        let output = |flags: &[&str], name: Option<&str>| {
+            let args = args(flags);
+            let mut output = vec![];
+            search_file(
+                &file,
+                &args,
+                &Matcher::new(&args),
+                name.is_some(),
+                &mut output,
+            )
+            .unwrap()
+            .unwrap();
+            String::from_utf8(output).unwrap().replace(&file, "b.txt")
+        };
+
+        assert_eq!(output(&["-n"], None), "2 needle\n");
+        assert_eq!(
+            output(&["-B", "1"], Some("b.txt")),
+            "b.txt-haystack\nb.txt:needle\n"
+        );
+        assert_eq!(output(&["-c"], Some("b.txt")), "b.txt:1\n");
+        assert_eq!(output(&["-l"], None), "b.txt\n");
+        assert_eq!(output(&["-L"], None), "");
+
+        // Бинарные файлы пропускаются
+        let binary = dir.path().join("data.bin").display().to_string();
+        let args = args(&[]);
+        assert!(
+            search_file(&binary, &args, &Matcher::new(&args), false, &mut vec![])
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    /// Вывод в несколько потоков совпадает с выводом в один поток,
+    /// в том числе для файлов, чей вывод не помещается в буфер очереди
+    #[test]
+    fn test_search_files_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        let big = "needle in a haystack\n".repeat(2 * OUTPUT_BUFFER_SIZE / 20);
+        let mut files = vec![];
+        for (i, content) in [&big, "needle\n", &big, "hay\n", "needle\nneedle\n"]
+            .iter()
+            .enumerate()
+        {
+            let path = dir.path().join(format!("{i}.txt"));
+            fs::write(&path, content).unwrap();
+            files.push(path.display().to_string());
+        }
+        files.push(dir.path().join("missing.txt").display().to_string());
+
+        let run = |threads: &str| {
+            let args = args(&["-n", "--threads", threads]);
+            let mut output = vec![];
+            let found = search_files(&files, &args, &Matcher::new(&args), true, &mut output);
+            (found.unwrap(), output)
+        };
+        let (found, expected) = run("1");
+        assert!(found);
+        assert!(expected.len() > 4 * OUTPUT_BUFFER_SIZE);
+        assert!(run("4") == (true, expected.clone()));
+        assert!(run("2") == (true, expected));
+    }
+
+    /// Вывод, закрытый после limit байт (как канал после `| head`)
+    struct ClosedOutput {
+        limit: usize,
+        written: usize,
+    }
+
+    impl Write for ClosedOutput {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            if self.written >= self.limit {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.written += data.len();
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Ошибка вывода останавливает поиск во всех потоках и возвращается
+    #[test]
+    fn test_search_files_closed_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<String> = (0..200)
+            .map(|i| {
+                let path = dir.path().join(format!("{i}.txt"));
+                fs::write(&path, "needle\n".repeat(10_000)).unwrap();
+                path.display().to_string()
+            })
+            .collect();
+
+        for threads in ["1", "4"] {
+            let args = args(&["--threads", threads]);
+            let mut output = ClosedOutput {
+                limit: 1,
+                written: 0,
+            };
+            let result = search_files(&files, &args, &Matcher::new(&args), true, &mut output);
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+        }
+    }
+
+    fn grep(flags: &[&str], content: &str) -> String {
+        let args = Args::parse_from(["t5"].iter().chain(flags)).prepare();
+        let mut output = vec![];
+        search(
+            content.as_bytes(),
+            &args,
+            &Matcher::new(&args),
+            "-",
+            None,
+            &mut output,
+        )
+        .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_patterns() {
+        let content = "foo\nfoo-bar\nfoobar\nBAR\n";
+        assert_eq!(
+            grep(&["-e", "foo", "-e", "bar"], content),
+            "foo\nfoo-bar\nfoobar\n"
+        );
+        assert_eq!(grep(&["-w", "foo"], content), "foo\nfoo-bar\n");
+        assert_eq!(grep(&["-x", "-i", "bar"], content), "BAR\n");
+        assert_eq!(grep(&["-F", "o-b"], content), "foo-bar\n");
+        assert_eq!(grep(&["-F", "-x", "foo-bar"], content), "foo-bar\n");
+        assert_eq!(
+            grep(&["-m", "2", "-A", "1", "foo"], content),
+            "foo\nfoo-bar\nfoobar\n"
+        );
+    }
+
+    #[test]
+    fn test_only_matching_and_color() {
+        let content = "a1b22c333\n";
+        assert_eq!(grep(&["-o", "-n", "\\d+"], content), "1 1\n1 22\n1 333\n");
+        assert_eq!(grep(&["-o", "-w", "\\d+"], content), "");
+        assert_eq!(
+            grep(&["--color=always", "\\d{2}"], content),
+            "a1b\x1b[01;31m\x1b[K22\x1b[m\x1b[Kc\x1b[01;31m\x1b[K33\x1b[m\x1b[K3\n"
+        );
+    }
+
+    #[test]
+    fn test_word_alternation() {
+        // Более длинная альтернатива с того же места, если короткая не целое слово
+        assert_eq!(grep(&["-w", "foo|foobar"], "foobar baz\n"), "foobar baz\n");
+        assert_eq!(grep(&["-o", "-w", "ab|abcd"], "abcd ab\n"), "abcd\nab\n");
+        assert_eq!(grep(&["-o", "-w", "a"], "a,a a_a\n"), "a\na\n");
+    }
+
+    /// Случаи из test/grep_output.txt: "Running `t5.exe ARGS`", затем ожидаемый вывод
+    #[test]
+    fn test_grep_fixtures() {
+        let fixtures = fs::read_to_string("test/grep_output.txt").unwrap();
+        let mut cases = vec![];
+        for line in fixtures.lines() {
+            match line.trim().strip_prefix("Running `") {
+                Some(command) => {
+                    let command = command.trim_end_matches('`');
+                    let flags = command.split_whitespace().skip(1).map(String::from);
+                    cases.push((flags.collect::<Vec<_>>(), vec![]));
+                }
+                None => match cases.last_mut() {
+                    Some((_, expected)) if !line.trim().is_empty() => {
+                        expected.push(line.trim_end().to_string())
+                    }
+                    _ => (),
+                },
             }
+        }
+        assert_eq!(cases.len(), 3);
 
-            println!("{}", lines[index]);
+        for (flags, expected) in cases {
+            let args = Args::parse_from(["t5".to_string()].iter().chain(&flags)).prepare();
+            let mut output = vec![];
+            search_file(
+                &args.paths[0],
+                &args,
+                &Matcher::new(&args),
+                false,
+                &mut output,
+            )
+            .unwrap();
+            let output = String::from_utf8(output).unwrap();
+            let output: Vec<&str> = output.lines().map(str::trim_end).collect();
+            assert_eq!(output, expected, "{flags:?}");
         }
     }
+
+    #[test]
+    fn test_context_groups() {
+        let content = "1\na\n2\n3\n4\n5\na\n6\na\n7\n";
+        assert_eq!(grep(&["-A", "1", "a"], content), "a\n2\n--\na\n6\na\n7\n");
+        assert_eq!(
+            grep(&["-B", "2", "a"], content),
+            "1\na\n--\n4\n5\na\n6\na\n"
+        );
+        assert_eq!(
+            grep(&["-n", "-C", "1", "-v", "\\d"], content),
+            "1 1\n2 a\n3 2\n--\n6 5\n7 a\n8 6\n9 a\n10 7\n"
+        );
+        // Без контекста разделителей нет
+        assert_eq!(grep(&["a"], content), "a\na\na\n");
+        // -m: хвост контекста после последнего выбранного
+        assert_eq!(
+            grep(&["-m", "2", "-A", "2", "a"], content),
+            "a\n2\n3\n--\na\n6\na\n"
+        );
+        // CRLF и последняя строка без перевода строки
+        assert_eq!(grep(&["b"], "a\r\nb\r\nab"), "b\nab\n");
+    }
 }
@@ -1,31 +1,37 @@
 // L2.6
 // https://linux.die.net/man/1/cut
 // echo "a|b|c|d|e|f|g|h|i|j|k" | cargo run --bin t6 -- -d "|" -f "1,5-8"
+// echo "привет, мир" | cargo run --bin t6 -- -c "-6,9-"
+// echo "a|b|c|d" | cargo run --bin t6 -- -d "|" -f 2 --complement --output-delimiter ","
+// cat users.csv | cargo run --bin t6 -- --csv -f name,email
 
 /*
 
-Usage: t6.exe [OPTIONS] --fields <FIELDS>
+Usage: t6.exe [OPTIONS] <--fields <FIELDS>|--bytes <BYTES>|--characters <CHARACTERS>>
 
 Options:
-  -d, --delimiter <DELIMITER>  Использовать собственный разделитель вместо табуляции [default: "\t"]
-  -f, --fields <FIELDS>        Выводит перечисленные через запятую столбцы (номер, диапазон). Например, "-2,4,6-8,10-"
-  -s, --separated              Только строки с указанным разделителем
-  -h, --help                   Print help
+  -f, --fields <FIELDS>                    Выводит перечисленные через запятую столбцы (номер, диапазон). Например, "-2,4,6-8,10-"
+  -b, --bytes <BYTES>                      Выводит перечисленные байты (номер, диапазон)
+  -c, --characters <CHARACTERS>            Выводит перечисленные символы UTF-8 (номер, диапазон)
+  -d, --delimiter <DELIMITER>              Использовать собственный разделитель вместо табуляции (с --csv - вместо запятой)
+  -s, --separated                          Только строки с указанным разделителем
+      --complement                         Выводить все, кроме перечисленного
+      --output-delimiter <DELIMITER>       Разделитель в выводе (по умолчанию для -f - разделитель ввода, для -b и -c - нет)
+  -z, --zero-terminated                    Строки разделены NUL, а не переводом строки
+      --csv                                Разбирать ввод как CSV (RFC 4180): кавычки, столбцы по имени из заголовка
+  -h, --help                               Print help
 
 */
 
 use clap::Parser;
 use std::{
-    cmp,
-    io::{self, BufRead},
+    borrow::Cow,
+    io::{self, BufRead, BufWriter, Write},
 };
 
 #[derive(Parser)]
+#[clap(group(clap::ArgGroup::new("mode").required(true).args(["fields", "bytes", "characters"])))]
 struct Args {
-    /// Использовать собственный разделитель вместо табуляции
-    #[clap(short, long, default_value = "\t")]
-    delimiter: String,
-
     /// Выводит перечисленные через запятую столбцы (номер, диапазон).
     /// Например, "-2,4,6-8,10-"
     #[clap(
@@ -33,72 +39,562 @@ struct Args {
         long,
         value_delimiter = ',', // автоматически резделяет аргумент
         allow_hyphen_values = true, // позволяет использовать минус в начале значений
-        required = true
     )]
-    fields: Vec<String>,
+    fields: Option<Vec<String>>,
+
+    /// Выводит перечисленные байты (номер, диапазон)
+    #[clap(short, long, value_delimiter = ',', allow_hyphen_values = true)]
+    bytes: Option<Vec<String>>,
+
+    /// Выводит перечисленные символы UTF-8 (номер, диапазон)
+    #[clap(short, long, value_delimiter = ',', allow_hyphen_values = true)]
+    characters: Option<Vec<String>>,
+
+    /// Использовать собственный разделитель вместо табуляции (с --csv - вместо запятой)
+    #[clap(short, long)]
+    delimiter: Option<String>,
 
     /// Только строки с указанным разделителем
     #[clap(short, long)]
     separated: bool,
+
+    /// Выводить все, кроме перечисленного
+    #[clap(long)]
+    complement: bool,
+
+    /// Разделитель в выводе (по умолчанию для -f - разделитель ввода, для -b и -c - нет)
+    #[clap(long, value_name = "DELIMITER")]
+    output_delimiter: Option<String>,
+
+    /// Строки разделены NUL, а не переводом строки
+    #[clap(short, long)]
+    zero_terminated: bool,
+
+    /// Разбирать ввод как CSV (RFC 4180): кавычки, столбцы по имени из заголовка
+    #[clap(long, requires = "fields")]
+    csv: bool,
+}
+
+impl Args {
+    fn delimiter(&self) -> &str {
+        match (&self.delimiter, self.csv) {
+            (Some(delimiter), _) => delimiter,
+            (None, true) => ",",
+            (None, false) => "\t",
+        }
+    }
+}
+
+/// Что вырезается из строки
+enum Mode {
+    Fields,
+    Bytes,
+    Characters,
+}
+
+impl Mode {
+    /// Режим и его диапазоны
+    fn from_args(args: &Args) -> (Self, &[String]) {
+        match (&args.fields, &args.bytes, &args.characters) {
+            (Some(specs), _, _) => (Mode::Fields, specs),
+            (_, Some(specs), _) => (Mode::Bytes, specs),
+            (_, _, Some(specs)) => (Mode::Characters, specs),
+            _ => unreachable!("clap requires one of -f, -b, -c"),
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
+    let terminator = if args.zero_terminated { b'\0' } else { b'\n' };
 
-    // Обработка указанный полей
-    // Перевод из строчных диапазонов в цифровые значения
-    // "-2,4,6-8,10-" -> [(1, 2), (4, 4), (6, 8), (10, 18446744073709551615)]
-    let mut fields = vec![];
-    for range in args.fields {
-        let range_first;
-        let range_last;
+    if args.csv {
+        let mut input = io::stdin().lock();
+        let mut output = BufWriter::new(io::stdout().lock());
+        if let Err(err) = cut_csv(&mut input, &mut output, &args, terminator) {
+            eprintln!("t6: {err}");
+            std::process::exit(1);
+        }
+        output.flush().expect("Couldn't write to stdout");
+        return;
+    }
+
+    let (mode, specs) = Mode::from_args(&args);
+    let ranges = match parse_ranges(specs) {
+        Ok(ranges) => Ranges::new(ranges, args.complement),
+        Err(err) => {
+            eprintln!("t6: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut output = BufWriter::new(io::stdout().lock());
+    let mut input = io::stdin().lock();
+    let mut record = vec![];
+    loop {
+        // Чтение записи до разделителя строк
+        record.clear();
+        if input
+            .read_until(terminator, &mut record)
+            .expect("Couldn't read stdin")
+            == 0
+        {
+            break;
+        }
+        if record.last() == Some(&terminator) {
+            record.pop();
+        }
 
-        // "-10" -> (1, 10)
-        if range.starts_with('-') {
-            let number = &range[1..];
-            range_first = 1;
-            range_last = number.parse::<usize>().expect("Couldn't parse field");
+        let Some(result) = cut_record(&record, &mode, &ranges, &args) else {
+            continue;
+        };
+
+        output.write_all(&result).expect("Couldn't write to stdout");
+        output
+            .write_all(&[terminator])
+            .expect("Couldn't write to stdout");
+    }
+    output.flush().expect("Couldn't write to stdout");
+}
+
+/// Перевод из строчных диапазонов в цифровые значения
+/// "-2,4,6-8,10-" -> [(1, 2), (4, 4), (6, 8), (10, 18446744073709551615)]
+fn parse_ranges(specs: &[String]) -> Result<Vec<(usize, usize)>, String> {
+    let parse = |number: &str| match number.parse::<usize>() {
+        Ok(0) => Err("fields and positions are numbered from 1".to_string()),
+        Ok(number) => Ok(number),
+        Err(_) => Err(format!("invalid field value '{number}'")),
+    };
+
+    let mut ranges = vec![];
+    for range in specs {
+        let (range_first, range_last) =
+            // "-10" -> (1, 10)
+            if let Some(number) = range.strip_prefix('-') {
+                (1, parse(number)?)
+            }
+            // "10-" -> (10, INF)
+            else if let Some(number) = range.strip_suffix('-') {
+                (parse(number)?, usize::MAX)
+            }
+            // "5-10" -> (5, 10)
+            else if let Some((first, last)) = range.split_once('-') {
+                (parse(first)?, parse(last)?)
+            }
+            // "10" -> (10, 10)
+            else {
+                let number = parse(range)?;
+                (number, number)
+            };
+
+        if range_first > range_last {
+            return Err(format!("invalid decreasing range '{range}'"));
         }
-        // "10-" -> (10, INF)
-        else if range.ends_with('-') {
-            let number = &range[..range.len() - 1];
-            range_first = number.parse::<usize>().expect("Couldn't parse field");
-            range_last = usize::MAX;
+        ranges.push((range_first, range_last));
+    }
+    Ok(ranges)
+}
+
+/// Выбранные номера (с 1): объединенные отсортированные диапазоны
+struct Ranges {
+    ranges: Vec<(usize, usize)>,
+    complement: bool,
+}
+
+impl Ranges {
+    fn new(mut ranges: Vec<(usize, usize)>, complement: bool) -> Self {
+        ranges.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = vec![];
+        for (first, last) in ranges {
+            match merged.last_mut() {
+                Some((_, merged_last)) if first <= merged_last.saturating_add(1) => {
+                    *merged_last = last.max(*merged_last)
+                }
+                _ => merged.push((first, last)),
+            }
         }
-        // "5-10" -> (5, 10)
-        else if range.contains('-') {
-            let numbers: Vec<&str> = range.split("-").collect();
-            range_first = numbers[0].parse::<usize>().expect("Couldn't parse field");
-            range_last = numbers[1].parse::<usize>().expect("Couldn't parse field");
+        Ranges {
+            ranges: merged,
+            complement,
         }
-        // "10" -> (10, 10)
-        else {
-            range_first = range.parse::<usize>().expect("Couldn't parse field");
-            range_last = range_first;
+    }
+
+    fn contains(&self, number: usize) -> bool {
+        let inside = self
+            .ranges
+            .iter()
+            .any(|&(first, last)| first <= number && number <= last);
+        inside ^ self.complement
+    }
+}
+
+/// Вырезка из одной записи. None - запись пропускается
+fn cut_record(record: &[u8], mode: &Mode, ranges: &Ranges, args: &Args) -> Option<Vec<u8>> {
+    match mode {
+        Mode::Fields => cut_fields(record, ranges, args),
+        Mode::Bytes => Some(cut_positions(record, record.len(), ranges, args, |i| i)),
+        Mode::Characters => {
+            // Границы символов в байтах: i-й символ - [bounds[i]; bounds[i + 1])
+            let line = String::from_utf8_lossy(record);
+            let bounds: Vec<usize> = line
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain([line.len()])
+                .collect();
+            let count = bounds.len() - 1;
+            Some(cut_positions(line.as_bytes(), count, ranges, args, |i| {
+                bounds[i]
+            }))
         }
+    }
+}
+
+/// Вырезка полей. None - строка пропускается (-s без разделителя).
+/// Строка делится по байтам разделителя и не декодируется как UTF-8
+fn cut_fields(line: &[u8], ranges: &Ranges, args: &Args) -> Option<Vec<u8>> {
+    let separator = args.delimiter().as_bytes();
+    let columns = split_bytes(line, separator);
 
-        fields.push((range_first, range_last));
+    // Строка без разделителя выводится целиком (как в GNU cut)
+    if columns.len() == 1 {
+        return match args.separated {
+            true => None,
+            false => Some(line.to_vec()),
+        };
     }
 
-    // Чтение и вывод
-    for line in io::stdin().lock().lines() {
-        let line = line.unwrap();
+    let delimiter = args
+        .output_delimiter
+        .as_deref()
+        .unwrap_or(args.delimiter())
+        .as_bytes();
+    let columns: Vec<&[u8]> = columns
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| ranges.contains(i + 1))
+        .map(|(_, column)| column)
+        .collect();
+    Some(columns.join(delimiter))
+}
 
-        // Проверка на наличие сепаратора
-        if args.separated && !line.contains(&args.delimiter) {
-            continue;
+/// Разбиение байтов по (возможно многобайтовому) разделителю
+fn split_bytes<'a>(line: &'a [u8], separator: &[u8]) -> Vec<&'a [u8]> {
+    if separator.is_empty() {
+        return vec![line];
+    }
+
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut i = 0;
+    while i + separator.len() <= line.len() {
+        if line[i..].starts_with(separator) {
+            parts.push(&line[start..i]);
+            i += separator.len();
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+    parts.push(&line[start..]);
+    parts
+}
+
+/// Вырезка байтов или символов из count позиций.
+/// offset(i) - байтовое смещение i-й позиции (с 0), offset(count) - конец строки.
+/// Между несмежными выбранными участками вставляется --output-delimiter
+fn cut_positions(
+    line: &[u8],
+    count: usize,
+    ranges: &Ranges,
+    args: &Args,
+    offset: impl Fn(usize) -> usize,
+) -> Vec<u8> {
+    let delimiter = args.output_delimiter.as_deref().unwrap_or_default();
+    let mut result = vec![];
+    let mut previous = None;
+    for i in (0..count).filter(|i| ranges.contains(i + 1)) {
+        if previous.is_some_and(|previous| previous + 1 != i) {
+            result.extend(delimiter.as_bytes());
+        }
+        result.extend(&line[offset(i)..offset(i + 1)]);
+        previous = Some(i);
+    }
+    result
+}
+
+/// Вырезка столбцов CSV. Первая запись - заголовок: по нему имена в -f
+/// переводятся в номера, и он выводится как обычная запись
+fn cut_csv(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    args: &Args,
+    terminator: u8,
+) -> Result<(), String> {
+    let mut delimiter_chars = args.delimiter().chars();
+    let (Some(delimiter), None) = (delimiter_chars.next(), delimiter_chars.next()) else {
+        return Err("the delimiter must be a single character in CSV mode".to_string());
+    };
+    let read = |input: &mut _| {
+        read_csv_record(input, delimiter, terminator)
+            .map_err(|err| format!("Couldn't read stdin: {err}"))
+    };
+
+    let Some(header) = read(input)? else {
+        return Ok(());
+    };
+    let specs = resolve_columns(args.fields.as_deref().unwrap_or_default(), &header)?;
+    let ranges = Ranges::new(parse_ranges(&specs)?, args.complement);
+
+    let mut record = Some(header);
+    while let Some(fields) = record {
+        if let Some(line) = cut_csv_record(&fields, &ranges, args) {
+            output
+                .write_all(line.as_bytes())
+                .and_then(|_| output.write_all(&[terminator]))
+                .map_err(|err| format!("Couldn't write to stdout: {err}"))?;
+        }
+        record = read(input)?;
+    }
+    Ok(())
+}
+
+/// Имена столбцов из заголовка -> номера, диапазоны остаются как есть
+fn resolve_columns(specs: &[String], header: &[String]) -> Result<Vec<String>, String> {
+    specs
+        .iter()
+        .map(|spec| {
+            if parse_ranges(std::slice::from_ref(spec)).is_ok() {
+                return Ok(spec.clone());
+            }
+            match header.iter().position(|name| name == spec) {
+                Some(index) => Ok((index + 1).to_string()),
+                None => Err(format!("unknown column '{spec}'")),
+            }
+        })
+        .collect()
+}
+
+/// Чтение одной записи CSV (RFC 4180).
+/// Поле в кавычках может содержать разделитель, перевод строки и кавычки ("" -> ")
+fn read_csv_record(
+    input: &mut impl BufRead,
+    delimiter: char,
+    terminator: u8,
+) -> io::Result<Option<Vec<String>>> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    // Последний символ поля добавлен внутри кавычек (CR из кавычек не отбрасывается)
+    let mut last_quoted = false;
+    let mut buffer = vec![];
+
+    loop {
+        // Запись может занимать несколько строк, если перевод строки в кавычках
+        buffer.clear();
+        if input.read_until(terminator, &mut buffer)? == 0 {
+            if fields.is_empty() && field.is_empty() && !in_quotes {
+                return Ok(None);
+            }
+            break;
         }
 
-        // Вывод нужных полей
-        let columns: Vec<&str> = line.split(&args.delimiter).collect();
-        for (range_start, range_finish) in fields.iter() {
-            let range_finish = cmp::min(columns.len(), *range_finish);
-            for i in *range_start..=range_finish {
-                if let Some(value) = columns.get(i - 1) {
-                    print!("{value}\t");
+        let chunk = String::from_utf8_lossy(&buffer);
+        let mut chars = chunk.chars().peekable();
+        while let Some(char) = chars.next() {
+            match (in_quotes, char) {
+                (true, '"') if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                    last_quoted = true;
+                }
+                (true, '"') => in_quotes = false,
+                (true, char) => {
+                    field.push(char);
+                    last_quoted = true;
+                }
+                (false, '"') => in_quotes = true,
+                (false, char) if char == delimiter => {
+                    fields.push(std::mem::take(&mut field));
+                    last_quoted = false;
+                }
+                (false, char) if char as u32 == terminator as u32 => (),
+                (false, char) => {
+                    field.push(char);
+                    last_quoted = false;
                 }
             }
         }
-        println!();
+
+        if !in_quotes {
+            break;
+        }
+    }
+
+    // CRLF в конце записи
+    if terminator == b'\n' && !last_quoted && field.ends_with('\r') {
+        field.pop();
+    }
+    fields.push(field);
+    Ok(Some(fields))
+}
+
+/// Вырезка столбцов одной записи CSV с обратным экранированием.
+/// None - запись пропускается (-s для записи из одного поля)
+fn cut_csv_record(fields: &[String], ranges: &Ranges, args: &Args) -> Option<String> {
+    let delimiter = args.output_delimiter.as_deref().unwrap_or(args.delimiter());
+
+    // Запись без разделителя выводится целиком (как в GNU cut)
+    if fields.len() == 1 {
+        return match args.separated {
+            true => None,
+            false => Some(quote_csv(&fields[0], delimiter).into_owned()),
+        };
+    }
+
+    let columns: Vec<Cow<str>> = fields
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| ranges.contains(i + 1))
+        .map(|(_, field)| quote_csv(field, delimiter))
+        .collect();
+    Some(columns.join(delimiter))
+}
+
+/// Поле в кавычках, если в нем есть разделитель, кавычки или перевод строки
+fn quote_csv<'a>(field: &'a str, delimiter: &str) -> Cow<'a, str> {
+    let special = |c: char| c == '"' || c == '\n' || c == '\r';
+    if field.contains(delimiter) || field.contains(special) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cut(flags: &[&str], input: &str) -> String {
+        let args = Args::parse_from(["t6"].iter().chain(flags));
+        let (mode, specs) = Mode::from_args(&args);
+        let ranges = Ranges::new(parse_ranges(specs).unwrap(), args.complement);
+        let result = cut_record(input.as_bytes(), &mode, &ranges, &args).unwrap_or_default();
+        String::from_utf8_lossy(&result).into_owned()
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        let specs = |specs: &[&str]| specs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            parse_ranges(&specs(&["-2", "4", "6-8", "10-"])),
+            Ok(vec![(1, 2), (4, 4), (6, 8), (10, usize::MAX)])
+        );
+        assert!(parse_ranges(&specs(&["0"])).is_err());
+        assert!(parse_ranges(&specs(&["3-1"])).is_err());
+        assert!(parse_ranges(&specs(&["a"])).is_err());
+    }
+
+    #[test]
+    fn test_fields() {
+        let line = "a|b|c|d|e|f|g|h|i|j|k";
+        assert_eq!(cut(&["-d", "|", "-f", "1,5-8"], line), "a|e|f|g|h");
+        assert_eq!(cut(&["-d", "|", "-f", "8-,-2,1"], line), "a|b|h|i|j|k");
+        assert_eq!(
+            cut(
+                &[
+                    "-d",
+                    "|",
+                    "-f",
+                    "2-",
+                    "--complement",
+                    "--output-delimiter",
+                    ","
+                ],
+                line
+            ),
+            "a"
+        );
+        assert_eq!(cut(&["-f", "2"], "no delimiter"), "no delimiter");
+        assert_eq!(cut(&["-f", "2", "-s"], "no delimiter"), "");
+    }
+
+    #[test]
+    fn test_fields_invalid_utf8() {
+        let args = Args::parse_from(["t6", "-d", ";", "-f", "2-"]);
+        let (mode, specs) = Mode::from_args(&args);
+        let ranges = Ranges::new(parse_ranges(specs).unwrap(), args.complement);
+        let record = b"a;\xff\xfe;c\x80";
+        assert_eq!(
+            cut_record(record, &mode, &ranges, &args),
+            Some(b"\xff\xfe;c\x80".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_bytes_and_characters() {
+        assert_eq!(cut(&["-b", "-2,4"], "abcdef"), "abd");
+        assert_eq!(cut(&["-b", "2-3", "--complement"], "abcdef"), "adef");
+        assert_eq!(
+            cut(&["-b", "1-2,5-", "--output-delimiter", ":"], "abcdef"),
+            "ab:ef"
+        );
+        assert_eq!(cut(&["-c", "-6,9-"], "привет, мир"), "приветмир");
+        assert_eq!(cut(&["-c", "2", "--complement"], "ёжик"), "ёик");
+        assert_eq!(
+            cut(&["-c", "1,3", "--output-delimiter", "|"], "日本語"),
+            "日|語"
+        );
+    }
+
+    fn csv(flags: &[&str], input: &str) -> Result<String, String> {
+        let args = Args::parse_from(["t6", "--csv"].iter().chain(flags));
+        let mut output = vec![];
+        cut_csv(&mut input.as_bytes(), &mut output, &args, b'\n')?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let input =
+            "id,name,email\r\n1,\"Doe, John\",john@example.com\r\n2,\"Say \"\"hi\"\"\nagain\",\r\n";
+        assert_eq!(
+            csv(&["-f", "2"], input).unwrap(),
+            "name\n\"Doe, John\"\n\"Say \"\"hi\"\"\nagain\"\n"
+        );
+        assert_eq!(
+            csv(&["-f", "1,3", "--output-delimiter", ";"], input).unwrap(),
+            "id;email\n1;john@example.com\n2;\n"
+        );
+        // Запятая в поле не требует кавычек с другим разделителем вывода
+        assert_eq!(
+            csv(&["-f", "2", "--output-delimiter", "\t"], "a,\"b,c\"\n").unwrap(),
+            "b,c\n"
+        );
+        assert_eq!(
+            csv(&["-d", ";", "-f", "2"], "a;\"b;c\";d\n").unwrap(),
+            "\"b;c\"\n"
+        );
+
+        // CR в кавычках - часть поля, CR после кавычек - часть CRLF
+        assert_eq!(
+            csv(&["-f", "2"], "a,\"b\r\"\nc,\"d\"\r\n").unwrap(),
+            "\"b\r\"\nd\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_header_names() {
+        let input = "id,name,email\n1,Ann,ann@example.com\n";
+        assert_eq!(
+            csv(&["-f", "email,name"], input).unwrap(),
+            "name,email\nAnn,ann@example.com\n"
+        );
+        assert_eq!(
+            csv(&["-f", "id", "--complement"], input).unwrap(),
+            "name,email\nAnn,ann@example.com\n"
+        );
+        assert_eq!(csv(&["-f", "1,name"], input).unwrap(), "id,name\n1,Ann\n");
+        assert!(csv(&["-f", "phone"], input).is_err());
+        assert_eq!(csv(&["-f", "1"], "").unwrap(), "");
     }
 }
@@ -1,126 +1,657 @@
 // L2.7
-// Разделяем файл по кускам (используя mutex и seek) и обрабатываем каждый кусок в отдельном потоке
-// Основной поток объединяет результаты и выводит JSON в консоль
+// Частотный анализ: символы, слова или n-граммы из файлов или STDIN
+// Разделяем каждый файл на куски по границам символов UTF-8 и обрабатываем каждый кусок в отдельном потоке
+// Каждый поток открывает собственный дескриптор файла, поэтому потоки не ждут друг друга
+// Основной поток объединяет результаты и выводит их в JSON, CSV или гистограммой
+// cargo run --bin t7 -- -t 4 --mode folded test/grep_input.txt
+// cargo run --bin t7 -- -u word -n 2 -k 10 -f histogram --mode folded test/grep_input.txt
+// cat test/grep_input.txt | cargo run --bin t7 -- -n 3 -k 5 -f csv
 
 /*
 
-Usage: t7.exe [OPTIONS] <FILE>
+Usage: t7.exe [OPTIONS] [FILE]...
 
 Arguments:
-  <FILE>
+  [FILE]...                Входные файлы ("-" или без файлов - STDIN) [default: -]
 
 Options:
   -t, --threads <THREADS>  Кол-во поток для выполнения задачи [default: 1]
+  -m, --mode <MODE>        Какие символы считать [default: ascii] [possible values: ascii, alphabetic, folded]
+  -u, --unit <UNIT>        Что считать: символы или слова (из символов MODE) [default: char] [possible values: char, word]
+  -n, --ngram <N>          Размер n-граммы (последовательности из N символов или слов) [default: 1]
+  -k, --top <K>            Вывести только K самых частых
+  -f, --format <FORMAT>    Формат вывода [default: json] [possible values: json, csv, histogram]
   -h, --help               Print help
 
+Режимы (MODE):
+  ascii       только латинские буквы ASCII, с учетом регистра
+  alphabetic  все буквы Unicode, с учетом регистра
+  folded      все буквы Unicode без учета регистра (в нижнем регистре)
+
+Формат JSON (FORMAT = json):
+  {"elapsed": "...", "total": N, "result": [{"count": N, "token": "..."}, ...]}
+  result - массив по убыванию частоты, а не объект {"символ": N} как в прежних версиях
+
 */
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use core::str;
 use serde_json::json;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     fs,
-    io::{Read, Seek, SeekFrom},
-    sync::{
-        mpsc::{self},
-        Arc, Mutex,
-    },
+    io::{self, Read, Seek, SeekFrom},
+    mem,
+    sync::mpsc,
     thread, time,
 };
 
+/// Размер блока чтения внутри куска
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Ширина столбца гистограммы
+const HISTOGRAM_WIDTH: usize = 50;
+
 #[derive(Parser)]
 struct Args {
-    file: String,
+    /// Входные файлы ("-" или без файлов - STDIN)
+    #[clap(id = "FILE", default_value = "-")]
+    files: Vec<String>,
 
     /// Кол-во поток для выполнения задачи
     #[clap(short, long, default_value = "1")]
     threads: usize,
+
+    /// Какие символы считать
+    #[clap(short, long, value_enum, default_value = "ascii")]
+    mode: Mode,
+
+    /// Что считать: символы или слова (из символов MODE)
+    #[clap(short, long, value_enum, default_value = "char")]
+    unit: Unit,
+
+    /// Размер n-граммы (последовательности из N символов или слов)
+    #[clap(short = 'n', long = "ngram", value_name = "N", default_value = "1",
+        value_parser = clap::value_parser!(u16).range(1..))]
+    ngram: u16,
+
+    /// Вывести только K самых частых
+    #[clap(short = 'k', long = "top", value_name = "K")]
+    top: Option<usize>,
+
+    /// Формат вывода
+    #[clap(short, long, value_enum, default_value = "json")]
+    format: Format,
+}
+
+/// Режим подсчета
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+enum Mode {
+    /// только латинские буквы ASCII, с учетом регистра
+    Ascii,
+    /// все буквы Unicode, с учетом регистра
+    Alphabetic,
+    /// все буквы Unicode без учета регистра (в нижнем регистре)
+    Folded,
+}
+
+/// Единица подсчета
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+enum Unit {
+    Char,
+    Word,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+enum Format {
+    Json,
+    Csv,
+    Histogram,
+}
+
+/// Параметры подсчета, общие для всех потоков
+#[derive(Clone, Copy, Debug)]
+struct Options {
+    mode: Mode,
+    unit: Unit,
+    ngram: usize,
 }
 
 fn main() {
     let start = time::Instant::now();
     let args = Args::parse();
-    let mut counter: HashMap<char, usize> = HashMap::with_capacity(26 * 2);
+    let options = Options {
+        mode: args.mode,
+        unit: args.unit,
+        ngram: args.ngram as usize,
+    };
 
+    // Счетчики всех файлов объединяются
+    let mut counter: HashMap<String, usize> = HashMap::new();
+    for file in args.files.iter() {
+        let local_counter = match file.as_str() {
+            "-" => count_stream(io::stdin().lock(), 0, u64::MAX, false, options),
+            file => count_file(file, args.threads.max(1), options),
+        };
+        for (k, v) in local_counter.expect("Couldn't read the file") {
+            *counter.entry(k).or_default() += v;
+        }
+    }
+    let elapsed = start.elapsed();
+
+    // Вывод результатов
+    let total = counter.values().sum();
+    let top = top_k(counter, args.top);
+    let output = match args.format {
+        Format::Json => format_json(&top, total, elapsed),
+        Format::Csv => format_csv(&top),
+        Format::Histogram => format_histogram(&top),
+    };
+    print!("{output}");
+}
+
+/// Подсчет в файле в threads потоков
+fn count_file(path: &str, threads: usize, options: Options) -> io::Result<HashMap<String, usize>> {
     // Открываем файл только на чтение и получаем его размер из метаданных (без чтения)
-    let file = Arc::new(Mutex::new(
-        fs::File::open(&args.file).expect("Couldn't open the file"),
-    ));
-    let bytes_count = file.lock().unwrap().metadata().unwrap().len() as usize;
-    let bytes_per_thread = bytes_count / args.threads;
-    let mut bytes_remains = bytes_count % args.threads;
-
-    if args.threads > 1 {
-        // Создаем канал
-        let (tx, rx) = mpsc::channel();
-        let tx = Arc::new(tx);
-
-        // Создаем потоки
-        for i in 0..args.threads {
-            let file_thread = Arc::clone(&file);
-            let tx_thread = Arc::clone(&tx);
-            thread::spawn(move || {
-                // Чтение части файла
-                let string = read_partition(&file_thread, i * bytes_per_thread, bytes_per_thread);
-
-                // Создание и отправка локального счетчика букв
-                let mut counter_thread: HashMap<char, usize> = HashMap::with_capacity(26 * 2);
-                count_letters(&mut counter_thread, &string);
-                tx_thread.send(counter_thread).expect("Failed to send data");
-            });
-        }
-        drop(tx); // сбрасываем лишний источник
-
-        // Получим ответ от потоков и объеденим все счетчики
-        for local_counter in rx {
-            for (k, v) in local_counter.iter() {
-                if counter.contains_key(k) {
-                    *counter.get_mut(k).unwrap() += v;
-                } else {
-                    counter.insert(*k, *v);
+    let mut file = fs::File::open(path)?;
+    let bytes_count = file.metadata()?.len();
+    let bounds = partition_bounds(&mut file, bytes_count, threads)?;
+
+    if threads == 1 {
+        return count_partition(path, 0, bytes_count, options);
+    }
+
+    // Создаем канал
+    let (tx, rx) = mpsc::channel();
+
+    // Создаем потоки, каждый со своим куском [start; end)
+    for bound in bounds.windows(2) {
+        let (start, end) = (bound[0], bound[1]);
+        let path = path.to_string();
+        let tx_thread = tx.clone();
+        thread::spawn(move || {
+            let counter_thread = count_partition(&path, start, end, options);
+            tx_thread.send(counter_thread).expect("Failed to send data");
+        });
+    }
+    drop(tx); // сбрасываем лишний источник
+
+    // Получим ответ от потоков и объеденим все счетчики
+    let mut counter: HashMap<String, usize> = HashMap::new();
+    for local_counter in rx {
+        for (k, v) in local_counter? {
+            *counter.entry(k).or_default() += v;
+        }
+    }
+    Ok(counter)
+}
+
+/// Границы кусков файла: [0, b1, ..., size].
+/// Граница сдвигается вперед с середины многобайтового символа UTF-8 на его конец
+fn partition_bounds(file: &mut fs::File, size: u64, parts: usize) -> io::Result<Vec<u64>> {
+    let mut bounds = vec![0];
+    for i in 1..parts as u64 {
+        let offset = (size * i / parts as u64).max(*bounds.last().unwrap());
+
+        // Продолжения символа UTF-8 имеют вид 0b10xxxxxx, их не больше трех подряд
+        let mut head = [0; 3];
+        file.seek(SeekFrom::Start(offset))?;
+        let read = file.read(&mut head)?;
+        let continuation = head[..read]
+            .iter()
+            .take_while(|&&byte| byte & 0xC0 == 0x80)
+            .count();
+
+        bounds.push(offset + continuation as u64);
+    }
+    bounds.push(size);
+    Ok(bounds)
+}
+
+/// Подсчет в куске файла [start; end) через собственный дескриптор.
+/// Слово, начатое в предыдущем куске, пропускается - его досчитывает предыдущий кусок
+fn count_partition(
+    path: &str,
+    start: u64,
+    end: u64,
+    options: Options,
+) -> io::Result<HashMap<String, usize>> {
+    let mut file = fs::File::open(path)?;
+
+    // Последний символ перед куском (не больше 4 байт UTF-8)
+    let mut skip_word = false;
+    if options.unit == Unit::Word && start > 0 {
+        let before = start.min(4);
+        let mut tail = vec![0; before as usize];
+        file.seek(SeekFrom::Start(start - before))?;
+        file.read_exact(&mut tail)?;
+        let last = String::from_utf8_lossy(&tail).chars().next_back();
+        skip_word = last.is_some_and(|ch| options.mode.accepts(ch));
+    }
+
+    file.seek(SeekFrom::Start(start))?;
+    count_stream(file, start, end, skip_word, options)
+}
+
+/// Подсчет n-грамм, которые начинаются в [start; end) потока, начинающегося со start.
+/// После end поток дочитывается, пока не закончатся начатые в куске n-граммы.
+/// Данные читаются блоками, неполный символ в конце блока переносится в следующий,
+/// некорректные последовательности UTF-8 пропускаются
+fn count_stream(
+    mut reader: impl Read,
+    start: u64,
+    end: u64,
+    skip_word: bool,
+    options: Options,
+) -> io::Result<HashMap<String, usize>> {
+    let mut counter = Counter::new(options, skip_word);
+    let mut buffer = vec![0; BLOCK_SIZE];
+    let mut offset = start; // смещение начала буфера в потоке
+    let mut pending = 0;
+
+    'read: loop {
+        let read = reader.read(&mut buffer[pending..])?;
+        if read == 0 {
+            break;
+        }
+
+        let len = pending + read;
+        let mut position = 0;
+        while position < len {
+            // [position; valid) - корректный UTF-8, next - продолжение после некорректных байт
+            let (valid, next) = match str::from_utf8(&buffer[position..len]) {
+                Ok(_) => (len, Some(len)),
+                Err(err) => {
+                    let valid = position + err.valid_up_to();
+                    (valid, err.error_len().map(|invalid| valid + invalid))
+                }
+            };
+
+            let string = str::from_utf8(&buffer[position..valid]).unwrap();
+            for (index, ch) in string.char_indices() {
+                if counter.in_range && offset + (position + index) as u64 >= end {
+                    counter.finish_range();
+                }
+                if !counter.needs_more() {
+                    break 'read;
+                }
+                counter.feed(ch);
+            }
+
+            match next {
+                Some(next) => position = next,
+                None => {
+                    // Символ продолжается в следующем блоке
+                    position = valid;
+                    break;
                 }
             }
         }
-    } else {
-        bytes_remains = bytes_count;
+
+        // Перенос неполного символа в начало буфера
+        pending = len - position;
+        buffer.copy_within(position..len, 0);
+        offset += position as u64;
     }
 
-    // Прочитаем остатки байт в основном потоке
-    let string = read_partition(&file, bytes_count - bytes_remains, bytes_remains);
-    count_letters(&mut counter, &string);
-    let elapsed = start.elapsed();
+    Ok(counter.finish())
+}
 
-    // Вывод результатов
+impl Mode {
+    /// Считается ли символ (буквой слова) в этом режиме
+    fn accepts(self, ch: char) -> bool {
+        match self {
+            Mode::Ascii => ch.is_ascii_alphabetic(),
+            Mode::Alphabetic | Mode::Folded => ch.is_alphabetic(),
+        }
+    }
+
+    /// Символ в этом режиме: в folded - в нижнем регистре (может стать несколькими)
+    fn fold(self, ch: char) -> impl Iterator<Item = char> {
+        let folded = match self {
+            Mode::Folded => Some(ch.to_lowercase()),
+            _ => None,
+        };
+        let original = folded.is_none().then_some(ch);
+        folded.into_iter().flatten().chain(original)
+    }
+}
+
+/// Потоковый счетчик n-грамм: символы -> токены -> окно из n токенов.
+/// Память выделяется только под новые n-граммы: символы при n = 1 считаются
+/// отдельно по char, а n-граммы собираются в переиспользуемом буфере
+struct Counter {
+    options: Options,
+    counts: HashMap<String, usize>,
+    /// Символы при n = 1
+    chars: HashMap<char, usize>,
+    /// Последние n символов (-u char)
+    char_window: VecDeque<char>,
+    /// Последние n слов (-u word), вытесненные строки переиспользуются для новых слов
+    word_window: VecDeque<String>,
+    /// Буфер для сборки n-граммы
+    ngram: String,
+    word: String,
+    /// Пропускать продолжение слова из предыдущего куска
+    skip_word: bool,
+    /// Символы еще внутри куска
+    in_range: bool,
+    /// Слово, начатое внутри куска, еще не закончено
+    word_in_range: bool,
+    /// Сколько токенов после куска еще нужно для начатых в нем n-грамм
+    lookahead: usize,
+}
+
+impl Counter {
+    fn new(options: Options, skip_word: bool) -> Self {
+        Counter {
+            options,
+            counts: HashMap::new(),
+            chars: HashMap::new(),
+            char_window: VecDeque::with_capacity(options.ngram),
+            word_window: VecDeque::with_capacity(options.ngram),
+            ngram: String::new(),
+            word: String::new(),
+            skip_word,
+            in_range: true,
+            word_in_range: false,
+            lookahead: 0,
+        }
+    }
+
+    fn feed(&mut self, ch: char) {
+        let mode = self.options.mode;
+        let accepted = mode.accepts(ch);
+        match self.options.unit {
+            Unit::Char if accepted => {
+                for ch in mode.fold(ch) {
+                    self.push_char(ch);
+                }
+            }
+            Unit::Char => (),
+            Unit::Word if accepted => {
+                if !self.skip_word {
+                    self.word.extend(mode.fold(ch));
+                }
+            }
+            Unit::Word => {
+                self.skip_word = false;
+                self.push_word();
+            }
+        }
+    }
+
+    fn push_char(&mut self, ch: char) {
+        self.count_token();
+        if self.options.ngram == 1 {
+            *self.chars.entry(ch).or_default() += 1;
+            return;
+        }
+
+        if self.char_window.len() == self.options.ngram {
+            self.char_window.pop_front();
+        }
+        self.char_window.push_back(ch);
+        if self.char_window.len() == self.options.ngram {
+            self.ngram.clear();
+            self.ngram.extend(&self.char_window);
+            increment(&mut self.counts, &self.ngram);
+        }
+    }
+
+    fn push_word(&mut self) {
+        if self.word.is_empty() {
+            return;
+        }
+        self.count_token();
+        if self.options.ngram == 1 {
+            increment(&mut self.counts, &self.word);
+            self.word.clear();
+            return;
+        }
+
+        // Слово уходит в окно, а буфер слова берется у вытесненного из окна
+        let recycled = match self.word_window.len() == self.options.ngram {
+            true => self.word_window.pop_front().unwrap(),
+            false => String::new(),
+        };
+        let word = mem::replace(&mut self.word, recycled);
+        self.word.clear();
+        self.word_window.push_back(word);
+        if self.word_window.len() == self.options.ngram {
+            self.ngram.clear();
+            for (i, word) in self.word_window.iter().enumerate() {
+                if i > 0 {
+                    self.ngram.push(' ');
+                }
+                self.ngram.push_str(word);
+            }
+            increment(&mut self.counts, &self.ngram);
+        }
+    }
+
+    /// Учет токена после конца куска
+    fn count_token(&mut self) {
+        if !self.in_range {
+            match self.word_in_range {
+                // Слово начато внутри куска - оно не входит в запас после куска
+                true => self.word_in_range = false,
+                false => self.lookahead = self.lookahead.saturating_sub(1),
+            }
+        }
+    }
+
+    /// Конец куска: дальше нужны только токены для начатых n-грамм
+    fn finish_range(&mut self) {
+        self.in_range = false;
+        self.word_in_range = !self.word.is_empty();
+        self.lookahead = self.options.ngram - 1;
+    }
+
+    fn needs_more(&self) -> bool {
+        self.in_range || self.word_in_range || self.lookahead > 0
+    }
+
+    fn finish(mut self) -> HashMap<String, usize> {
+        if self.needs_more() {
+            self.push_word();
+        }
+        for (ch, count) in self.chars {
+            *self.counts.entry(ch.to_string()).or_default() += count;
+        }
+        self.counts
+    }
+}
+
+/// Увеличение счетчика токена. Строка ключа создается только для нового токена
+fn increment(counts: &mut HashMap<String, usize>, token: &str) {
+    match counts.get_mut(token) {
+        Some(count) => *count += 1,
+        None => {
+            counts.insert(token.to_string(), 1);
+        }
+    }
+}
+
+/// K самых частых по убыванию (при равенстве - по алфавиту)
+fn top_k(counter: HashMap<String, usize>, k: Option<usize>) -> Vec<(String, usize)> {
+    let mut top: Vec<(String, usize)> = counter.into_iter().collect();
+    top.sort_unstable_by(|(a_token, a_count), (b_token, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_token.cmp(b_token))
+    });
+    top.truncate(k.unwrap_or(usize::MAX));
+    top
+}
+
+fn format_json(top: &[(String, usize)], total: usize, elapsed: time::Duration) -> String {
+    let result: Vec<_> = top
+        .iter()
+        .map(|(token, count)| json!({ "token": token, "count": count }))
+        .collect();
     let result = json!({
         "elapsed": format!("{:?}", elapsed),
-        "result": counter,
+        "total": total,
+        "result": result,
     });
-    println!("{}", serde_json::to_string_pretty(&result).unwrap());
+    format!("{}\n", serde_json::to_string_pretty(&result).unwrap())
+}
+
+fn format_csv(top: &[(String, usize)]) -> String {
+    let mut output = String::from("token,count\n");
+    for (token, count) in top {
+        // Экранирование по RFC 4180
+        if token.contains([',', '"', '\n', '\r']) {
+            output += &format!("\"{}\",{count}\n", token.replace('"', "\"\""));
+        } else {
+            output += &format!("{token},{count}\n");
+        }
+    }
+    output
 }
 
-/// Чтение области файла с диска в оперативную память
-fn read_partition(file: &Arc<Mutex<fs::File>>, start: usize, size: usize) -> String {
-    let mut buffer = vec![0; size];
-    let mut file_mutex = file.lock().unwrap();
-    file_mutex.seek(SeekFrom::Start(start as u64)).unwrap();
-    file_mutex.read_exact(&mut buffer).unwrap();
-    drop(file_mutex);
+fn format_histogram(top: &[(String, usize)]) -> String {
+    let width = top.iter().map(|(token, _)| token.chars().count()).max();
+    let max_count = top.iter().map(|(_, count)| *count).max();
+    let (Some(width), Some(max_count)) = (width, max_count) else {
+        return String::new();
+    };
 
-    // Конвертация байт в utf8 строку
-    str::from_utf8(&buffer).expect("Invalid UTF-8").to_string()
+    let mut output = String::new();
+    for (token, count) in top {
+        // Ненулевое кол-во всегда видно хотя бы одним делением
+        let bar = (count * HISTOGRAM_WIDTH).div_ceil(max_count);
+        output += &format!("{token:<width$} │{} {count}\n", "█".repeat(bar));
+    }
+    output
 }
 
-fn count_letters(counter: &mut HashMap<char, usize>, string: &str) {
-    for ch in string.chars() {
-        if !ch.is_ascii_alphabetic() {
-            continue;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
 
-        if counter.contains_key(&ch) {
-            *counter.get_mut(&ch).unwrap() += 1;
-        } else {
-            counter.insert(ch, 1);
+    fn options(mode: Mode, unit: Unit, ngram: usize) -> Options {
+        Options { mode, unit, ngram }
+    }
+
+    fn count(content: &[u8], threads: usize, options: Options) -> HashMap<String, usize> {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(content).unwrap();
+        count_file(file.path().to_str().unwrap(), threads, options).unwrap()
+    }
+
+    fn counts(pairs: &[(&str, usize)]) -> HashMap<String, usize> {
+        pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_modes() {
+        let content = "AbA ЁжЁ ß 1_!".as_bytes();
+        let ascii = count(content, 1, options(Mode::Ascii, Unit::Char, 1));
+        assert_eq!(ascii, counts(&[("A", 2), ("b", 1)]));
+
+        let alphabetic = count(content, 1, options(Mode::Alphabetic, Unit::Char, 1));
+        assert_eq!(alphabetic["Ё"], 2);
+        assert_eq!(alphabetic["ß"], 1);
+        assert_eq!(alphabetic.len(), 5);
+
+        let folded = count(content, 1, options(Mode::Folded, Unit::Char, 1));
+        assert_eq!(
+            folded,
+            counts(&[("a", 2), ("b", 1), ("ё", 2), ("ж", 1), ("ß", 1)])
+        );
+    }
+
+    #[test]
+    fn test_words_and_ngrams() {
+        let content = "To be, or not to BE: that is the question.".as_bytes();
+        let words = count(content, 1, options(Mode::Folded, Unit::Word, 1));
+        assert_eq!(words["to"], 2);
+        assert_eq!(words["be"], 2);
+        assert_eq!(words.values().sum::<usize>(), 10);
+
+        let bigrams = count(content, 1, options(Mode::Folded, Unit::Word, 2));
+        assert_eq!(bigrams["to be"], 2);
+        assert_eq!(bigrams.values().sum::<usize>(), 9);
+
+        let trigrams = count(b"abcab", 1, options(Mode::Ascii, Unit::Char, 3));
+        assert_eq!(trigrams, counts(&[("abc", 1), ("bca", 1), ("cab", 1)]));
+    }
+
+    // Границы кусков попадают на середины многобайтовых символов, слов и n-грамм
+    #[test]
+    fn test_partitions_match_sequential() {
+        let content = "aЖ€😀b слово,  другое\nслово ".repeat(23);
+        for options in [
+            options(Mode::Alphabetic, Unit::Char, 1),
+            options(Mode::Folded, Unit::Char, 3),
+            options(Mode::Alphabetic, Unit::Word, 1),
+            options(Mode::Folded, Unit::Word, 3),
+        ] {
+            let expected = count(content.as_bytes(), 1, options);
+            for threads in 2..=40 {
+                assert_eq!(
+                    count(content.as_bytes(), threads, options),
+                    expected,
+                    "{options:?} threads={threads}"
+                );
+            }
         }
     }
+
+    #[test]
+    fn test_block_boundaries_and_invalid_bytes() {
+        // Символ на границе блока чтения и некорректные байты
+        let mut content = vec![b'x'; BLOCK_SIZE - 1];
+        content.extend("ЖЖ".as_bytes());
+        content.extend([0xFF, 0xC3, b'y']);
+        let options = options(Mode::Alphabetic, Unit::Char, 1);
+        let counter = count(&content, 1, options);
+        let expected: HashMap<String, usize> = [
+            ("x".to_string(), BLOCK_SIZE - 1),
+            ("Ж".to_string(), 2),
+            ("y".to_string(), 1),
+        ]
+        .into();
+        assert_eq!(counter, expected);
+        assert_eq!(count(&content, 3, options), counter);
+
+        // STDIN читается тем же потоковым счетчиком
+        let stdin = count_stream(content.as_slice(), 0, u64::MAX, false, options).unwrap();
+        assert_eq!(stdin, counter);
+    }
+
+    #[test]
+    fn test_output_formats() {
+        let top = top_k(
+            counts(&[("b", 2), ("a", 2), ("c", 4), ("x,\"y", 1)]),
+            Some(3),
+        );
+        assert_eq!(
+            top,
+            vec![
+                ("c".to_string(), 4),
+                ("a".to_string(), 2),
+                ("b".to_string(), 2)
+            ]
+        );
+        assert_eq!(format_csv(&top), "token,count\nc,4\na,2\nb,2\n");
+        assert_eq!(
+            format_csv(&[("x,\"y".to_string(), 1)]),
+            "token,count\n\"x,\"\"y\",1\n"
+        );
+
+        let histogram = format_histogram(&top);
+        let lines: Vec<&str> = histogram.lines().collect();
+        assert_eq!(lines[0], format!("c │{} 4", "█".repeat(HISTOGRAM_WIDTH)));
+        assert_eq!(
+            lines[1],
+            format!("a │{} 2", "█".repeat(HISTOGRAM_WIDTH / 2))
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&format_json(&top, 9, time::Duration::ZERO)).unwrap();
+        assert_eq!(json["total"], 9);
+        assert_eq!(json["result"][0], json!({ "token": "c", "count": 4 }));
+    }
 }
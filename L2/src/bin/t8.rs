@@ -1,121 +1,1034 @@
 // L2.8
 // Пример: echo ../../../ | cd - | pwd
+// Пример: ls | grep t | wc -l
+// Пример: cd ~/projects && echo "home is $HOME" || echo 'failed: $?' ; pwd
+// Пример: sleep 30 & jobs ; fg %1
 
 use core::str;
 use std::{
     env::{self},
-    io::{self, Write},
-    process::{self},
+    fmt, fs,
+    io::{self, Read, Write},
+    iter::Peekable,
+    process::{self, Child, ChildStdout, ExitStatus, Stdio},
+    str::CharIndices,
+    thread,
 };
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 enum Command {
-    HELP,
-    QUIT,
-    PWD,
-    CD,
-    ECHO,
-    PS,
-    KILL,
+    Help,
+    Quit,
+    Pwd,
+    Cd,
+    Echo,
+    Ps,
+    Kill,
+    Jobs,
+    Fg,
+    Bg,
+}
+
+/// Часть слова. Подстановки выполняются непосредственно перед запуском команды
+#[derive(Debug, PartialEq, Clone)]
+enum WordPart {
+    Literal(String),
+    /// $NAME, ${NAME}, $?
+    Variable(String),
+    /// "~" в начале слова
+    Home,
+}
+
+/// Слово командной строки. Кавычки и экранирование уже обработаны лексером
+#[derive(Debug, PartialEq, Clone, Default)]
+struct Word {
+    parts: Vec<WordPart>,
+    /// Слово содержит кавычки: пустое значение остается аргументом
+    quoted: bool,
+}
+
+impl Word {
+    fn push(&mut self, ch: char) {
+        match self.parts.last_mut() {
+            Some(WordPart::Literal(literal)) => literal.push(ch),
+            _ => self.parts.push(WordPart::Literal(ch.to_string())),
+        }
+    }
+
+    /// Значение слова после подстановок. None - пустая подстановка без кавычек
+    fn expand(&self, variable: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+        let mut result = String::new();
+        for part in self.parts.iter() {
+            match part {
+                WordPart::Literal(literal) => result += literal,
+                // Неизвестная переменная - пустая строка
+                WordPart::Variable(name) => result += &variable(name).unwrap_or_default(),
+                WordPart::Home => result += &variable("HOME").unwrap_or_else(|| "~".to_string()),
+            }
+        }
+        (self.quoted || !result.is_empty()).then_some(result)
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in self.parts.iter() {
+            match part {
+                WordPart::Literal(literal) => write!(f, "{literal}")?,
+                WordPart::Variable(name) => write!(f, "${{{name}}}")?,
+                WordPart::Home => write!(f, "~")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Лексема командной строки
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(Word),
+    /// |
+    Pipe,
+    /// &&
+    And,
+    /// ||
+    Or,
+    /// ;
+    Semicolon,
+    /// &
+    Background,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "\"{word}\""),
+            Token::Pipe => write!(f, "'|'"),
+            Token::And => write!(f, "'&&'"),
+            Token::Or => write!(f, "'||'"),
+            Token::Semicolon => write!(f, "';'"),
+            Token::Background => write!(f, "'&'"),
+        }
+    }
+}
+
+/// AST: простая команда - программа и ее аргументы
+#[derive(Debug, PartialEq)]
+struct SimpleCommand {
+    words: Vec<Word>,
+}
+
+/// AST: команды, соединенные через "|"
+#[derive(Debug, PartialEq)]
+struct Pipeline {
+    commands: Vec<SimpleCommand>,
+}
+
+/// Условие выполнения следующего пайплайна
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Connector {
+    /// && - если предыдущий завершился успешно
+    And,
+    /// || - если предыдущий завершился с ошибкой
+    Or,
+}
+
+/// AST: пайплайны, соединенные через "&&" и "||"
+#[derive(Debug, PartialEq)]
+struct AndOrList {
+    first: Pipeline,
+    rest: Vec<(Connector, Pipeline)>,
+    /// Список завершается "&" - запуск в фоне
+    background: bool,
+}
+
+/// AST: вся строка - списки, разделенные ";" или "&"
+type Script = Vec<AndOrList>;
+
+/// Звено пайплайна: встроенная команда или внешняя программа
+enum Stage {
+    Builtin(Command, Vec<String>),
+    External(String, Vec<String>),
+}
+
+/// Что подается на STDIN очередного звена пайплайна
+enum Input {
+    /// STDIN терминала
+    Terminal,
+    /// Вывод встроенной команды
+    Text(String),
+    /// STDOUT предыдущего процесса
+    Pipe(ChildStdout),
 }
 
 fn main() {
+    // Код завершения последней команды ($?)
+    let mut status = 0;
+    let mut jobs = Jobs::interactive();
+
     loop {
+        // Сообщения о фоновых задачах
+        jobs.update();
+
         // Приглашение к вводу
         print!("\n(L2.8) $ ");
         io::stdout().flush().unwrap();
 
         // Чтение и парсинг ввода
         let mut buffer: String = String::new();
-        io::stdin().read_line(&mut buffer).unwrap();
-        let commands = parse(&buffer.trim());
-
-        // Выполнение списка команд (пайплайна)
-        let mut last_result: Option<Result<String, String>> = None;
-        for (command, mut argument) in commands {
-            // Получение вывода предыдущей команды
-            let last_stdout;
-            if let Some(last_output) = last_result.clone() {
-                last_stdout = last_output.unwrap();
-                argument = last_stdout.as_str();
-            }
-
-            // Обрабатываем специальный аргумент (stdout -> stdin)
-            if argument == "-" {
-                last_result = Some(Err(format!(
-                    "Command \"{:?}\" waited for input from stdin, but it was not provided",
-                    command
-                )));
-                break;
-            }
-
-            // Выполнение команды
-            let result = match command {
-                Command::QUIT => return,
-                Command::HELP => print_help(),
-                Command::PWD => print_workdir(),
-                Command::PS => print_processes(),
-                Command::ECHO => print_echo(argument),
-                Command::CD => change_directory(argument),
-                Command::KILL => kill_process(argument),
-            };
-            last_result = Some(result);
+        if io::stdin().read_line(&mut buffer).unwrap() == 0 {
+            return; // EOF (Ctrl-D)
+        }
+        let script = match parse(buffer.trim()) {
+            Ok(script) => script,
+            Err(err) => {
+                eprintln!("syntax error: {err}");
+                status = 2;
+                continue;
+            }
+        };
+
+        // Выполнение
+        match run_script(script, status, &mut jobs) {
+            Some(0) => status = 0,
+            Some(code) => {
+                eprintln!("[exit status {code}]");
+                status = code;
+            }
+            None => return, // quit
+        }
+    }
+}
+
+/// Лексер + парсер: строка -> AST
+fn parse(input: &str) -> Result<Script, String> {
+    parse_script(tokenize(input)?)
+}
+
+/// Лексер: разбор кавычек ('...' - без подстановок, "..." - с подстановками),
+/// экранирования "\", подстановок $VAR, ${VAR}, $? и "~" в начале слова
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = input.char_indices().peekable();
+
+    // Текущее слово
+    let mut word: Option<Word> = None;
+    let finish_word = |word: &mut Option<Word>, tokens: &mut Vec<Token>| {
+        if let Some(word) = word.take() {
+            tokens.push(Token::Word(word));
         }
+    };
+
+    while let Some((position, ch)) = chars.next() {
+        match ch {
+            ch if ch.is_whitespace() => finish_word(&mut word, &mut tokens),
+
+            // Операторы
+            '|' | '&' | ';' => {
+                finish_word(&mut word, &mut tokens);
+                let token = match (ch, chars.peek().map(|&(_, next)| next)) {
+                    ('|', Some('|')) => Token::Or,
+                    ('|', _) => Token::Pipe,
+                    ('&', Some('&')) => Token::And,
+                    ('&', _) => Token::Background,
+                    _ => Token::Semicolon,
+                };
+                if matches!(token, Token::Or | Token::And) {
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+
+            // Одинарные кавычки: все буквально
+            '\'' => {
+                let word = word.get_or_insert_with(Word::default);
+                word.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) => break,
+                        Some((_, ch)) => word.push(ch),
+                        None => return Err(format!("unterminated ' at position {position}")),
+                    }
+                }
+            }
+
+            // Двойные кавычки: экранирование \" \\ \$ \` и подстановки переменных
+            '"' => {
+                let word = word.get_or_insert_with(Word::default);
+                word.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, ch @ ('"' | '\\' | '$' | '`'))) => word.push(ch),
+                            Some((_, ch)) => {
+                                word.push('\\');
+                                word.push(ch);
+                            }
+                            None => return Err(format!("unterminated \" at position {position}")),
+                        },
+                        Some((_, '$')) => push_variable(&mut chars, word)?,
+                        Some((_, ch)) => word.push(ch),
+                        None => return Err(format!("unterminated \" at position {position}")),
+                    }
+                }
+            }
+
+            // Экранирование следующего символа
+            '\\' => match chars.next() {
+                Some((_, ch)) => word.get_or_insert_with(Word::default).push(ch),
+                None => return Err("unexpected end of input after '\\'".to_string()),
+            },
+
+            '$' => push_variable(&mut chars, word.get_or_insert_with(Word::default))?,
 
-        // Вывод
-        if let Some(result) = last_result {
-            match result {
-                Ok(stdout) => println!("{stdout}"),
-                Err(stderr) => eprintln!("{stderr}"),
+            // "~" в начале слова - домашняя директория
+            '~' if word.is_none()
+                && chars
+                    .peek()
+                    .is_none_or(|&(_, next)| next == '/' || is_word_end(next)) =>
+            {
+                word = Some(Word {
+                    parts: vec![WordPart::Home],
+                    quoted: false,
+                });
             }
+
+            ch => word.get_or_insert_with(Word::default).push(ch),
         }
     }
+    finish_word(&mut word, &mut tokens);
+
+    Ok(tokens)
 }
 
-/// Поддержка пайплайнов
-fn parse(input: &str) -> Vec<(Command, &str)> {
-    // Разделение по пайпам
-    let input_commands = input.split("|");
-    let mut commands = vec![];
+/// Символ, завершающий слово без кавычек
+fn is_word_end(ch: char) -> bool {
+    ch.is_whitespace() || matches!(ch, '|' | '&' | ';')
+}
 
-    // Парсинг каждой команды по-отдельности
-    for input_command in input_commands {
-        if let Some(command) = parse_command(&input_command.trim()) {
-            commands.push(command);
+/// Подстановка после "$": $NAME, ${NAME}, $?. Одиночный "$" остается как есть
+fn push_variable(chars: &mut Peekable<CharIndices>, word: &mut Word) -> Result<(), String> {
+    let is_name_char = |ch: char| ch.is_ascii_alphanumeric() || ch == '_';
+
+    let name = match chars.peek().map(|&(_, ch)| ch) {
+        Some('?') => {
+            chars.next();
+            "?".to_string()
         }
-        // Если команду не удалось распарсить - выводим только помощь
-        else {
-            commands.clear();
-            commands.push((Command::HELP, ""));
-            break;
+        Some('{') => {
+            let (position, _) = chars.next().unwrap();
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '}')) => break,
+                    Some((_, ch)) if is_name_char(ch) || (ch == '?' && name.is_empty()) => {
+                        name.push(ch)
+                    }
+                    Some((_, ch)) => return Err(format!("bad substitution: unexpected '{ch}'")),
+                    None => return Err(format!("unterminated ${{ at position {position}")),
+                }
+            }
+            if name.is_empty() {
+                return Err("bad substitution: ${}".to_string());
+            }
+            name
+        }
+        Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => {
+            let mut name = String::new();
+            while let Some(&(_, ch)) = chars.peek() {
+                if !is_name_char(ch) {
+                    break;
+                }
+                name.push(ch);
+                chars.next();
+            }
+            name
+        }
+        _ => {
+            word.push('$');
+            return Ok(());
         }
+    };
+
+    word.parts.push(WordPart::Variable(name));
+    Ok(())
+}
+
+/// Парсер: лексемы -> AST
+fn parse_script(tokens: Vec<Token>) -> Result<Script, String> {
+    let mut tokens = tokens.into_iter().peekable();
+    let mut script = vec![];
+
+    while tokens.peek().is_some() {
+        let mut list = parse_and_or(&mut tokens)?;
+        match tokens.next() {
+            Some(Token::Semicolon) | None => (),
+            // Фоновая задача - один пайплайн
+            Some(Token::Background) if list.rest.is_empty() => list.background = true,
+            Some(Token::Background) => {
+                return Err("'&' after a list with '&&' or '||' is not supported".to_string())
+            }
+            Some(token) => return Err(format!("unexpected {token}")),
+        }
+        script.push(list);
     }
 
-    commands
+    Ok(script)
 }
 
-/// Парсинг простейших команд
-fn parse_command(input: &str) -> Option<(Command, &str)> {
-    // Простейшие команды
+fn parse_and_or(tokens: &mut Peekable<impl Iterator<Item = Token>>) -> Result<AndOrList, String> {
+    let first = parse_pipeline(tokens)?;
+    let mut rest = vec![];
+    loop {
+        let connector = match tokens.peek() {
+            Some(Token::And) => Connector::And,
+            Some(Token::Or) => Connector::Or,
+            _ => break,
+        };
+        tokens.next();
+        rest.push((connector, parse_pipeline(tokens)?));
+    }
+    Ok(AndOrList {
+        first,
+        rest,
+        background: false,
+    })
+}
+
+fn parse_pipeline(tokens: &mut Peekable<impl Iterator<Item = Token>>) -> Result<Pipeline, String> {
+    let mut commands = vec![parse_command(tokens)?];
+    while tokens.next_if_eq(&Token::Pipe).is_some() {
+        commands.push(parse_command(tokens)?);
+    }
+    Ok(Pipeline { commands })
+}
+
+fn parse_command(
+    tokens: &mut Peekable<impl Iterator<Item = Token>>,
+) -> Result<SimpleCommand, String> {
+    let mut words = vec![];
+    while let Some(Token::Word(_)) = tokens.peek() {
+        if let Some(Token::Word(word)) = tokens.next() {
+            words.push(word);
+        }
+    }
+
+    if words.is_empty() {
+        return Err(match tokens.peek() {
+            Some(token) => format!("unexpected {token}"),
+            None => "unexpected end of input".to_string(),
+        });
+    }
+    Ok(SimpleCommand { words })
+}
+
+/// Выполнение AST. Результат - код завершения последнего выполненного пайплайна,
+/// None - команда выхода
+fn run_script(script: Script, mut status: i32, jobs: &mut Jobs) -> Option<i32> {
+    for list in script {
+        status = run_pipeline(list.first, status, list.background, jobs)?;
+        for (connector, pipeline) in list.rest {
+            let run = match connector {
+                Connector::And => status == 0,
+                Connector::Or => status != 0,
+            };
+            if run {
+                status = run_pipeline(pipeline, status, false, jobs)?;
+            }
+        }
+    }
+    Some(status)
+}
+
+/// Подстановки в словах команды. status - значение для $?
+fn expand_words(command: &SimpleCommand, status: i32) -> Vec<String> {
+    let variable = |name: &str| match name {
+        "?" => Some(status.to_string()),
+        name => env::var(name).ok(),
+    };
+    command
+        .words
+        .iter()
+        .filter_map(|word| word.expand(&variable))
+        .collect()
+}
+
+/// Встроенная команда или внешняя программа. None - пустая команда
+fn resolve(mut words: Vec<String>) -> Option<Stage> {
+    if words.is_empty() {
+        return None;
+    }
+    let name = words.remove(0);
+    let builtin = match name.as_str() {
+        "quit" => Command::Quit,
+        "pwd" => Command::Pwd,
+        "ps" => Command::Ps,
+        "help" => Command::Help,
+        "echo" => Command::Echo,
+        "cd" => Command::Cd,
+        "kill" => Command::Kill,
+        "jobs" => Command::Jobs,
+        "fg" => Command::Fg,
+        "bg" => Command::Bg,
+        // Все остальное - внешняя программа
+        _ => return Some(Stage::External(name, words)),
+    };
+    Some(Stage::Builtin(builtin, words))
+}
+
+/// Выполнение пайплайна: STDOUT каждого звена подключается к STDIN следующего.
+/// Процессы пайплайна - одна задача (группа процессов), background - не ждать ее.
+/// status - код предыдущей команды для $?.
+/// Результат - код завершения последнего звена, None - команда выхода
+fn run_pipeline(pipeline: Pipeline, status: i32, background: bool, jobs: &mut Jobs) -> Option<i32> {
+    let count = pipeline.commands.len();
+    let take_terminal = !background && jobs.is_interactive();
+    let mut input = Input::Terminal;
+    let mut processes: Vec<Process> = vec![];
+    let mut pgid = None;
+    let mut text = vec![];
+    let mut last_status = 0;
+    let mut last_spawned = false;
+
+    for (index, command) in pipeline.commands.into_iter().enumerate() {
+        let is_last = index + 1 == count;
+        let words = expand_words(&command, status);
+        text.push(words.join(" "));
+        let Some(stage) = resolve(words) else {
+            // Пустая команда (например, пустая переменная) ничего не делает
+            last_status = 0;
+            last_spawned = false;
+            input = Input::Text(String::new());
+            continue;
+        };
+        match stage {
+            Stage::Builtin(command, args) => {
+                // Специальный аргумент "-" читается из STDIN (stdout -> stdin)
+                let argument = match (args.as_slice(), input) {
+                    ([dash], Input::Terminal) if dash == "-" => Err(format!(
+                        "Command \"{:?}\" waited for input from stdin, but it was not provided",
+                        command
+                    )),
+                    ([dash], Input::Text(text)) if dash == "-" => Ok(text.trim().to_string()),
+                    ([dash], Input::Pipe(mut pipe)) if dash == "-" => {
+                        let mut text = String::new();
+                        match pipe.read_to_string(&mut text) {
+                            Ok(_) => Ok(text.trim().to_string()),
+                            Err(err) => Err(err.to_string()),
+                        }
+                    }
+                    (args, _) => Ok(args.join(" ")),
+                };
+
+                // Выполнение команды
+                let mut job_status = None;
+                let result = argument.and_then(|argument| match command {
+                    Command::Quit => Err(String::new()),
+                    Command::Help => print_help(),
+                    Command::Pwd => print_workdir(),
+                    Command::Ps => print_processes(&argument),
+                    Command::Echo => print_echo(&argument),
+                    Command::Cd => change_directory(&argument),
+                    Command::Kill => kill_process(&argument, jobs),
+                    Command::Jobs => Ok(jobs.list()),
+                    Command::Bg => jobs.resume(&argument),
+                    Command::Fg => jobs.foreground(&argument).map(|status| {
+                        job_status = Some(status);
+                        String::new()
+                    }),
+                });
+                if command == Command::Quit {
+                    if let Some(pgid) = pgid {
+                        jobs.wait(Job::new(pgid, text.join(" | "), processes, None), false);
+                    }
+                    return None;
+                }
+
+                // Вывод
+                last_status = job_status.unwrap_or(if result.is_ok() { 0 } else { 1 });
+                last_spawned = false;
+                input = match (result, is_last) {
+                    (Ok(stdout), true) => {
+                        println!("{stdout}");
+                        Input::Terminal
+                    }
+                    (Ok(stdout), false) => Input::Text(stdout + "\n"),
+                    (Err(stderr), _) => {
+                        eprintln!("{stderr}");
+                        Input::Text(String::new())
+                    }
+                };
+            }
+            Stage::External(program, args) => {
+                let (child, status) = spawn(&program, &args, input, is_last, pgid, take_terminal);
+                last_status = status;
+                last_spawned = child.is_some();
+                input = Input::Text(String::new());
+                if let Some(mut child) = child {
+                    if let Some(stdout) = child.stdout.take() {
+                        input = Input::Pipe(stdout);
+                    }
+                    // Первый процесс задает группу процессов задачи
+                    if pgid.is_none() {
+                        pgid = Some(child.id());
+                        if take_terminal {
+                            jobs.set_terminal(child.id());
+                        }
+                    }
+                    processes.push(Process {
+                        child,
+                        status: None,
+                        stopped: None,
+                    });
+                }
+            }
+        }
+    }
+
+    // Ни один процесс не запущен - код последнего звена
+    let Some(pgid) = pgid else {
+        return Some(last_status);
+    };
+
+    // Код завершения пайплайна - код последнего звена
+    let job = Job::new(
+        pgid,
+        text.join(" | "),
+        processes,
+        (!last_spawned).then_some(last_status),
+    );
+    if background {
+        jobs.add(job);
+        return Some(0);
+    }
+    Some(jobs.wait(job, false))
+}
+
+/// Запуск внешней программы (fork/exec). Код 127 - программа не найдена.
+/// Процесс попадает в группу pgid (None - новая группа), take_terminal -
+/// группа процесса становится группой переднего плана терминала
+fn spawn(
+    program: &str,
+    args: &[String],
+    input: Input,
+    is_last: bool,
+    pgid: Option<u32>,
+    take_terminal: bool,
+) -> (Option<Child>, i32) {
+    let mut command = process::Command::new(program);
+    command.args(args);
+    command.stdout(if is_last {
+        Stdio::inherit()
+    } else {
+        Stdio::piped()
+    });
+
+    let mut text = None;
     match input {
-        "quit" => return Some((Command::QUIT, "")),
-        "pwd" => return Some((Command::PWD, "")),
-        "ps" => return Some((Command::PS, "")),
-        "help" => return Some((Command::HELP, "")),
-        _ => (),
+        Input::Terminal => command.stdin(Stdio::inherit()),
+        Input::Pipe(pipe) => command.stdin(Stdio::from(pipe)),
+        Input::Text(stdin) => {
+            text = Some(stdin);
+            command.stdin(Stdio::piped())
+        }
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(pgid.map_or(0, |pgid| pgid as i32));
+        // SAFETY: между fork и exec вызываются только async-signal-safe функции
+        unsafe {
+            command.pre_exec(move || {
+                // SIGTTOU еще игнорируется, поэтому процесс может забрать терминал сам
+                if take_terminal {
+                    libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+                }
+                for signal in JOB_SIGNALS {
+                    libc::signal(signal, libc::SIG_DFL);
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (pgid, take_terminal);
+
+    match command.spawn() {
+        Ok(mut child) => {
+            // Вывод встроенной команды пишется в отдельном потоке, чтобы не заблокироваться
+            if let (Some(text), Some(mut stdin)) = (text, child.stdin.take()) {
+                thread::spawn(move || stdin.write_all(text.as_bytes()));
+            }
+            (Some(child), 0)
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            eprintln!("{program}: command not found");
+            (None, 127)
+        }
+        Err(err) => {
+            eprintln!("{program}: {err}");
+            (None, 126)
+        }
+    }
+}
+
+/// Сигналы управления задачами: шелл их игнорирует, запущенные программы - нет
+#[cfg(unix)]
+const JOB_SIGNALS: [libc::c_int; 5] = [
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTSTP,
+    libc::SIGTTIN,
+    libc::SIGTTOU,
+];
+
+/// Процесс задачи
+struct Process {
+    child: Child,
+    /// Код завершения, None - процесс еще работает
+    status: Option<ExitStatus>,
+    /// Номер сигнала, которым процесс остановлен
+    stopped: Option<i32>,
+}
+
+/// Состояние задачи
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum JobState {
+    Running,
+    /// Номер сигнала остановки
+    Stopped(i32),
+    /// Код завершения последнего звена
+    Done(i32),
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobState::Running => f.pad("Running"),
+            JobState::Stopped(_) => f.pad("Stopped"),
+            JobState::Done(0) => f.pad("Done"),
+            JobState::Done(code) => f.pad(&format!("Exit {code}")),
+        }
+    }
+}
+
+/// Задача: процессы одного пайплайна в общей группе процессов
+struct Job {
+    /// Номер задачи (%N), 0 - еще не в таблице задач
+    id: usize,
+    /// Группа процессов - PID первого процесса
+    pgid: u32,
+    /// Текст команды для jobs
+    text: String,
+    processes: Vec<Process>,
+    /// Код последнего звена, если это не запущенный процесс (встроенная команда, ошибка запуска)
+    last_status: Option<i32>,
+}
+
+impl Job {
+    fn new(pgid: u32, text: String, processes: Vec<Process>, last_status: Option<i32>) -> Self {
+        Job {
+            id: 0,
+            pgid,
+            text,
+            processes,
+            last_status,
+        }
+    }
+
+    fn state(&self) -> JobState {
+        if let Some(signal) = self.processes.iter().find_map(|process| process.stopped) {
+            return JobState::Stopped(signal);
+        }
+        if self
+            .processes
+            .iter()
+            .any(|process| process.status.is_none())
+        {
+            return JobState::Running;
+        }
+        match (self.last_status, self.processes.last()) {
+            (Some(code), _) => JobState::Done(code),
+            (
+                None,
+                Some(Process {
+                    status: Some(status),
+                    ..
+                }),
+            ) => JobState::Done(exit_code(*status)),
+            _ => JobState::Done(0),
+        }
+    }
+
+    /// Опрос процессов. block - ждать, пока каждый процесс не завершится или не остановится
+    fn poll(&mut self, block: bool) {
+        for process in self.processes.iter_mut() {
+            if process.status.is_none() && !(block && process.stopped.is_some()) {
+                wait_process(process, block);
+            }
+        }
+    }
+
+    /// Продолжение остановленной задачи (SIGCONT всей группе процессов)
+    fn resume(&mut self) -> Result<(), String> {
+        #[cfg(unix)]
+        if unsafe { libc::kill(-(self.pgid as libc::pid_t), libc::SIGCONT) } < 0 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        for process in self.processes.iter_mut() {
+            process.stopped = None;
+        }
+        Ok(())
+    }
+}
+
+/// Ожидание изменения состояния процесса (завершение, остановка, продолжение)
+#[cfg(unix)]
+fn wait_process(process: &mut Process, block: bool) {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut flags = libc::WUNTRACED | libc::WCONTINUED;
+    if !block {
+        flags |= libc::WNOHANG;
+    }
+    loop {
+        let mut raw = 0;
+        let pid = unsafe { libc::waitpid(process.child.id() as libc::pid_t, &mut raw, flags) };
+        if pid == 0 {
+            return; // WNOHANG: состояние не изменилось
+        }
+        if pid < 0 {
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            process.status = Some(ExitStatus::default());
+            return;
+        }
+        if libc::WIFSTOPPED(raw) {
+            process.stopped = Some(libc::WSTOPSIG(raw));
+            return;
+        }
+        if libc::WIFCONTINUED(raw) {
+            process.stopped = None;
+            continue;
+        }
+        process.stopped = None;
+        process.status = Some(ExitStatus::from_raw(raw));
+        return;
+    }
+}
+
+/// Ожидание завершения процесса (без управления задачами процессы не останавливаются)
+#[cfg(not(unix))]
+fn wait_process(process: &mut Process, block: bool) {
+    let result = match block {
+        true => process.child.wait().map(Some),
+        false => process.child.try_wait(),
     };
+    process.status = result.unwrap_or(Some(ExitStatus::default()));
+}
+
+/// Таблица задач шелла
+#[derive(Default)]
+struct Jobs {
+    /// Фоновые и остановленные задачи. Последняя - текущая (%+)
+    list: Vec<Job>,
+    /// Шелл управляет терминалом: группа процессов шелла и режимы терминала
+    #[cfg(unix)]
+    terminal: Option<(libc::pid_t, libc::termios)>,
+}
+
+impl Jobs {
+    /// Таблица задач интерактивного шелла: если STDIN - терминал, шелл становится
+    /// лидером своей группы процессов и забирает терминал
+    fn interactive() -> Self {
+        #[allow(unused_mut)]
+        let mut jobs = Jobs::default();
+
+        #[cfg(unix)]
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) == 1 {
+                // Запущен в фоне - ждем, пока нас не выведут на передний план
+                while libc::tcgetpgrp(libc::STDIN_FILENO) != libc::getpgrp() {
+                    libc::kill(-libc::getpgrp(), libc::SIGTTIN);
+                }
+
+                // Ctrl-C, Ctrl-Z и т.п. терминал отправляет группе переднего плана, а не шеллу
+                for signal in JOB_SIGNALS {
+                    libc::signal(signal, libc::SIG_IGN);
+                }
+
+                libc::setpgid(0, 0);
+                let pgid = libc::getpgrp();
+                libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+                let mut modes = std::mem::zeroed();
+                libc::tcgetattr(libc::STDIN_FILENO, &mut modes);
+                jobs.terminal = Some((pgid, modes));
+            }
+        }
+
+        jobs
+    }
+
+    fn is_interactive(&self) -> bool {
+        #[cfg(unix)]
+        return self.terminal.is_some();
+        #[cfg(not(unix))]
+        false
+    }
+
+    /// Передача терминала группе процессов
+    fn set_terminal(&self, pgid: u32) {
+        #[cfg(unix)]
+        if self.terminal.is_some() {
+            unsafe { libc::tcsetpgrp(libc::STDIN_FILENO, pgid as libc::pid_t) };
+        }
+        #[cfg(not(unix))]
+        let _ = pgid;
+    }
+
+    /// Возврат терминала шеллу с его режимами (задача могла их поменять)
+    fn restore_terminal(&self) {
+        #[cfg(unix)]
+        if let Some((pgid, modes)) = &self.terminal {
+            unsafe {
+                libc::tcsetpgrp(libc::STDIN_FILENO, *pgid);
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, modes);
+            }
+        }
+    }
+
+    /// Добавление задачи в таблицу с очередным номером
+    fn push(&mut self, mut job: Job) -> &Job {
+        if job.id == 0 {
+            job.id = self.list.iter().map(|job| job.id).max().unwrap_or_default() + 1;
+        }
+        self.list.push(job);
+        self.list.last().unwrap()
+    }
+
+    /// Запуск задачи в фоне
+    fn add(&mut self, job: Job) {
+        let job = self.push(job);
+        eprintln!("[{}] {}", job.id, job.pgid);
+    }
+
+    /// Ожидание задачи переднего плана: терминал на это время принадлежит ее группе.
+    /// resume - продолжить остановленную задачу.
+    /// Остановленная задача (Ctrl-Z) попадает в таблицу задач, код - 128 + номер сигнала
+    fn wait(&mut self, mut job: Job, resume: bool) -> i32 {
+        if self.is_interactive() {
+            self.set_terminal(job.pgid);
+        }
+        if resume {
+            if let Err(err) = job.resume() {
+                eprintln!("fg: {err}");
+            }
+        }
+        job.poll(true);
+        self.restore_terminal();
 
-    // Параметризированные команды
-    let (command, argument) = input.split_once(" ").unwrap_or_default();
-    match command {
-        "echo" => return Some((Command::ECHO, argument)),
-        "cd" => return Some((Command::CD, argument)),
-        "kill" => return Some((Command::KILL, argument)),
-        _ => (),
+        match job.state() {
+            JobState::Done(code) => code,
+            state => {
+                let code = match state {
+                    JobState::Stopped(signal) => 128 + signal,
+                    _ => 0,
+                };
+                let job = self.push(job);
+                eprintln!("\n[{}]+  {:<8} {}", job.id, state, job.text);
+                code
+            }
+        }
+    }
+
+    /// Строка задачи для jobs: номер, "+" у текущей задачи, состояние и команда
+    fn describe(&self, index: usize) -> String {
+        let job = &self.list[index];
+        let current = if index + 1 == self.list.len() {
+            '+'
+        } else {
+            ' '
+        };
+        format!("[{}]{current}  {:<8} {}", job.id, job.state(), job.text)
+    }
+
+    /// Опрос фоновых задач перед приглашением: сообщения о завершенных и остановленных
+    fn update(&mut self) {
+        for index in 0..self.list.len() {
+            let before = self.list[index].state();
+            self.list[index].poll(false);
+            let after = self.list[index].state();
+            if after != before && after != JobState::Running {
+                eprintln!("{}", self.describe(index));
+            }
+        }
+        self.list
+            .retain(|job| !matches!(job.state(), JobState::Done(_)));
+    }
+
+    /// Встроенная команда jobs. Завершенные задачи выводятся один раз
+    fn list(&mut self) -> String {
+        for job in self.list.iter_mut() {
+            job.poll(false);
+        }
+        let lines: Vec<String> = (0..self.list.len())
+            .map(|index| self.describe(index))
+            .collect();
+        self.list
+            .retain(|job| !matches!(job.state(), JobState::Done(_)));
+        lines.join("\n")
+    }
+
+    /// Поиск задачи: "%N", "N" или пустая строка - текущая задача
+    fn find(&self, spec: &str) -> Result<usize, String> {
+        if spec.is_empty() || spec == "%+" || spec == "%%" {
+            return match self.list.is_empty() {
+                true => Err("no current job".to_string()),
+                false => Ok(self.list.len() - 1),
+            };
+        }
+        let no_such_job = || format!("{spec}: no such job");
+        let id: usize = spec
+            .strip_prefix('%')
+            .unwrap_or(spec)
+            .parse()
+            .map_err(|_| no_such_job())?;
+        self.list
+            .iter()
+            .position(|job| job.id == id)
+            .ok_or_else(no_such_job)
     }
 
-    eprintln!("Wrong command: \"{}\"", input);
-    None
+    /// Встроенная команда fg: задача переходит на передний план. Результат - ее код
+    fn foreground(&mut self, spec: &str) -> Result<i32, String> {
+        let index = self.find(spec)?;
+        let job = self.list.remove(index);
+        println!("{}", job.text);
+        Ok(self.wait(job, true))
+    }
+
+    /// Встроенная команда bg: остановленная задача продолжается в фоне
+    fn resume(&mut self, spec: &str) -> Result<String, String> {
+        let index = self.find(spec)?;
+        let job = &mut self.list[index];
+        job.resume()?;
+        Ok(format!("[{}] {} &", job.id, job.text))
+    }
+}
+
+/// Код завершения процесса (128 + номер сигнала, если процесс убит сигналом)
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
 }
 
 /// Список доступных команд
@@ -123,11 +1036,24 @@ fn print_help() -> Result<String, String> {
     Ok("Available commands:\n  \
        help             - Print this helper\n  \
        pwd              - Print current working directory\n  \
-       ps               - Print running processes\n  \
+       ps [options]     - Print processes: PID, PPID, state, elapsed ms, RSS, command\n                     \
+                          -f TEXT, -p PPID, -S STATE - filter; -s pid|ppid|state|time|rss|cmd, -r - sort\n  \
        echo <argument>  - Display a line of text\n  \
        cd <argument>    - Change working directory\n  \
-       kill <argument>  - Kill a process\n  \
-       quit             - Exit the current shell"
+       kill [-SIG] PID  - Send a signal (name or number, default TERM) to a process or %job\n  \
+       jobs             - List background and stopped jobs\n  \
+       fg [%N], bg [%N] - Continue a job in the foreground / background\n  \
+       quit             - Exit the current shell\n\
+     Any other command is started as an external program.\n\
+     Syntax:\n  \
+       a | b            - Pipe stdout of a to stdin of b\n  \
+       a && b, a || b   - Run b if a succeeded / failed\n  \
+       a ; b            - Run a, then b\n  \
+       a &              - Run a in the background (Ctrl-Z stops a foreground job)\n  \
+       '...' \"...\"      - Quote text (no expansion inside single quotes)\n  \
+       \\c               - Escape a character\n  \
+       $VAR ${VAR} $? ~ - Environment variable, last exit status, home directory\n  \
+       -                - As a builtin's only argument: read it from stdin"
         .to_string())
 }
 
@@ -146,6 +1072,9 @@ fn print_echo(input: &str) -> Result<String, String> {
 
 /// Смена директории (в качестве аргумента могут быть то-то и то)
 fn change_directory(path: &str) -> Result<String, String> {
+    // Без аргумента - домашняя директория
+    let home = env::var("HOME").unwrap_or_default();
+    let path = if path.is_empty() { home.as_str() } else { path };
     match env::set_current_dir(path) {
         Ok(_) => Ok("".to_string()),
         Err(err) => Err(err.to_string()),
@@ -153,25 +1082,362 @@ fn change_directory(path: &str) -> Result<String, String> {
 }
 
 /// Выводит общую информацию по запущенным процессам в формате id процесса, название, время работы в мсек.
-fn print_processes() -> Result<String, String> {
-    if cfg!(windows) {
+/// Аргументы: [-f TEXT] [-p PPID] [-S STATE] [-s pid|ppid|state|time|rss|cmd] [-r]
+fn print_processes(argument: &str) -> Result<String, String> {
+    let options = PsOptions::parse(argument)?;
+    if cfg!(target_os = "linux") {
+        let mut processes = read_processes().map_err(|err| format!("ps: /proc: {err}"))?;
+        options.apply(&mut processes);
+        Ok(format_processes(&processes))
+    } else if cfg!(windows) {
         start_subprocess("tasklist", &[]) // Нет аргументов для фильтрации
     } else {
-        start_subprocess("ps", &["-eo", "pid,comm,etime"])
+        start_subprocess("ps", &["-eo", "pid,ppid,state,etime,rss,comm"])
     }
 }
 
-/// Убить процесс, переданный в качестве аргумента
-fn kill_process(id_str: &str) -> Result<String, String> {
-    if let Err(_) = id_str.parse::<usize>() {
-        return Err(format!("Invalid pid: \"{id_str}\""));
+/// Процесс из /proc/<pid>
+#[derive(Debug, PartialEq)]
+struct ProcessInfo {
+    pid: u32,
+    ppid: u32,
+    state: char,
+    /// Время работы, мсек
+    elapsed: u64,
+    /// Resident set size, КиБ
+    rss: u64,
+    command: String,
+}
+
+/// Поле сортировки ps
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum SortKey {
+    Pid,
+    Ppid,
+    State,
+    Elapsed,
+    Rss,
+    Command,
+}
+
+/// Ключи встроенной команды ps
+#[derive(Debug, PartialEq)]
+struct PsOptions {
+    /// -f: подстрока команды
+    filter: Option<String>,
+    /// -p: только дочерние процессы PPID
+    ppid: Option<u32>,
+    /// -S: только процессы в состоянии (R, S, D, Z, T, ...)
+    state: Option<char>,
+    /// -s: поле сортировки
+    sort: SortKey,
+    /// -r: обратный порядок
+    reverse: bool,
+}
+
+impl PsOptions {
+    fn parse(argument: &str) -> Result<Self, String> {
+        let usage =
+            "usage: ps [-f TEXT] [-p PPID] [-S STATE] [-s pid|ppid|state|time|rss|cmd] [-r]";
+        let mut options = PsOptions {
+            filter: None,
+            ppid: None,
+            state: None,
+            sort: SortKey::Pid,
+            reverse: false,
+        };
+
+        let mut args = argument.split_whitespace();
+        while let Some(arg) = args.next() {
+            if arg == "-r" {
+                options.reverse = true;
+                continue;
+            }
+            let value = match arg {
+                "-f" | "-p" | "-S" | "-s" => args.next().ok_or(usage)?,
+                _ => return Err(usage.to_string()),
+            };
+            match arg {
+                "-f" => options.filter = Some(value.to_string()),
+                "-p" => options.ppid = Some(value.parse().map_err(|_| usage)?),
+                "-S" => options.state = Some(value.parse().map_err(|_| usage)?),
+                _ => {
+                    options.sort = match value {
+                        "pid" => SortKey::Pid,
+                        "ppid" => SortKey::Ppid,
+                        "state" => SortKey::State,
+                        "time" => SortKey::Elapsed,
+                        "rss" => SortKey::Rss,
+                        "cmd" => SortKey::Command,
+                        _ => return Err(usage.to_string()),
+                    }
+                }
+            }
+        }
+
+        Ok(options)
     }
 
+    /// Фильтрация и сортировка списка процессов
+    fn apply(&self, processes: &mut Vec<ProcessInfo>) {
+        processes.retain(|process| {
+            self.filter
+                .as_ref()
+                .is_none_or(|filter| process.command.contains(filter.as_str()))
+                && self.ppid.is_none_or(|ppid| process.ppid == ppid)
+                && self.state.is_none_or(|state| process.state == state)
+        });
+        processes.sort_by(|a, b| {
+            let order = match self.sort {
+                SortKey::Pid => a.pid.cmp(&b.pid),
+                SortKey::Ppid => a.ppid.cmp(&b.ppid),
+                SortKey::State => a.state.cmp(&b.state),
+                SortKey::Elapsed => a.elapsed.cmp(&b.elapsed),
+                SortKey::Rss => a.rss.cmp(&b.rss),
+                SortKey::Command => a.command.cmp(&b.command),
+            };
+            // При равенстве - по PID
+            let order = order.then(a.pid.cmp(&b.pid));
+            if self.reverse {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+    }
+}
+
+/// Чтение всех процессов из /proc. Процессы, завершившиеся во время чтения, пропускаются
+#[cfg(unix)]
+fn read_processes() -> io::Result<Vec<ProcessInfo>> {
+    // Время работы системы (сек) и размеры "тика" и страницы памяти
+    let uptime = fs::read_to_string("/proc/uptime")?;
+    let uptime: f64 = uptime
+        .split_whitespace()
+        .next()
+        .and_then(|uptime| uptime.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad /proc/uptime"))?;
+    let uptime = (uptime * 1000.0) as u64;
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64;
+
+    let mut processes = vec![];
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        else {
+            continue; // не процесс
+        };
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        let Some(stat) = parse_stat(&stat) else {
+            continue;
+        };
+        let cmdline = fs::read(entry.path().join("cmdline")).unwrap_or_default();
+
+        processes.push(ProcessInfo {
+            pid,
+            ppid: stat.ppid,
+            state: stat.state,
+            elapsed: uptime.saturating_sub(stat.start_time * 1000 / ticks),
+            rss: stat.rss * page_size / 1024,
+            command: format_cmdline(&cmdline, stat.name),
+        });
+    }
+    Ok(processes)
+}
+
+#[cfg(not(unix))]
+fn read_processes() -> io::Result<Vec<ProcessInfo>> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Нужные поля /proc/<pid>/stat
+#[derive(Debug, PartialEq)]
+struct Stat<'a> {
+    name: &'a str,
+    state: char,
+    ppid: u32,
+    /// Момент запуска, тики с загрузки системы
+    start_time: u64,
+    /// Resident set size, страницы
+    rss: u64,
+}
+
+/// Разбор /proc/<pid>/stat: "pid (comm) state ppid ...".
+/// Имя может содержать пробелы и скобки, поэтому оно - до последней ")"
+fn parse_stat(stat: &str) -> Option<Stat<'_>> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let name = stat.get(open + 1..close)?;
+    // Поля после имени, нумерация с 3-го поля (state)
+    let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+    Some(Stat {
+        name,
+        state: fields.first()?.chars().next()?,
+        ppid: fields.get(1)?.parse().ok()?,
+        start_time: fields.get(19)?.parse().ok()?,
+        rss: fields.get(21)?.parse().ok()?,
+    })
+}
+
+/// Командная строка из /proc/<pid>/cmdline (аргументы через "\0").
+/// Пустая у потоков ядра - тогда имя в квадратных скобках, как в ps
+fn format_cmdline(cmdline: &[u8], name: &str) -> String {
+    let args: Vec<String> = cmdline
+        .split(|&byte| byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    match args.is_empty() {
+        true => format!("[{name}]"),
+        false => args.join(" "),
+    }
+}
+
+/// Таблица процессов
+fn format_processes(processes: &[ProcessInfo]) -> String {
+    let mut output = format!(
+        "{:>7} {:>7} {} {:>12} {:>10} {}",
+        "PID", "PPID", "S", "ELAPSED(ms)", "RSS(KiB)", "COMMAND"
+    );
+    for process in processes {
+        output += &format!(
+            "\n{:>7} {:>7} {} {:>12} {:>10} {}",
+            process.pid, process.ppid, process.state, process.elapsed, process.rss, process.command
+        );
+    }
+    output
+}
+
+/// Убить процесс, переданный в качестве аргумента.
+/// Аргументы: [-SIGNAL | -s SIGNAL] PID|%JOB..., -l - список сигналов.
+/// Сигнал задается именем (TERM, SIGTERM) или номером, по умолчанию SIGTERM
+fn kill_process(argument: &str, jobs: &Jobs) -> Result<String, String> {
+    let usage = "usage: kill [-SIGNAL | -s SIGNAL] PID|%JOB... | kill -l";
+    let mut args = argument.split_whitespace().peekable();
+
     if cfg!(windows) {
-        start_subprocess("taskkill", &["/F", "/pid", id_str])
-    } else {
-        start_subprocess("kill", &[id_str])
+        let id_str = args.next().ok_or(usage)?;
+        if id_str.parse::<usize>().is_err() {
+            return Err(format!("Invalid pid: \"{id_str}\""));
+        }
+        return start_subprocess("taskkill", &["/F", "/pid", id_str]);
+    }
+
+    // Номер сигнала
+    let signal = match args.peek().copied() {
+        Some("-l") => {
+            let names: Vec<String> = SIGNALS
+                .iter()
+                .map(|(name, number)| format!("{number:>2}) SIG{name}"))
+                .collect();
+            return Ok(names.join("\n"));
+        }
+        Some("-s") => {
+            args.next();
+            parse_signal(args.next().ok_or(usage)?).map_err(|err| format!("kill: {err}"))?
+        }
+        Some(arg) if arg.starts_with('-') => {
+            args.next();
+            parse_signal(&arg[1..]).map_err(|err| format!("kill: {err}"))?
+        }
+        _ => parse_signal("TERM")?,
+    };
+
+    let targets: Vec<&str> = args.collect();
+    if targets.is_empty() {
+        return Err(usage.to_string());
+    }
+
+    // Обработка всех целей, ошибки собираются
+    let mut errors = vec![];
+    for target in targets {
+        if let Err(err) = send_signal(target, signal, jobs) {
+            errors.push(format!("kill: {target}: {err}"));
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(String::new()),
+        false => Err(errors.join("\n")),
+    }
+}
+
+/// Имена и номера сигналов для kill
+#[cfg(unix)]
+const SIGNALS: [(&str, libc::c_int); 19] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+];
+
+#[cfg(not(unix))]
+const SIGNALS: [(&str, i32); 0] = [];
+
+/// Сигнал по имени (TERM, SIGTERM, term) или номеру (15, 0 - проверка существования)
+fn parse_signal(signal: &str) -> Result<i32, String> {
+    if let Ok(number) = signal.parse::<i32>() {
+        return match number == 0 || SIGNALS.iter().any(|&(_, known)| known == number) {
+            true => Ok(number),
+            false => Err(format!("{signal}: invalid signal specification")),
+        };
     }
+    let name = signal.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    SIGNALS
+        .iter()
+        .find(|&&(known, _)| known == name)
+        .map(|&(_, number)| number)
+        .ok_or_else(|| format!("{signal}: invalid signal specification"))
+}
+
+/// Отправка сигнала процессу (PID) или всей группе процессов задачи (%N)
+#[cfg(unix)]
+fn send_signal(target: &str, signal: i32, jobs: &Jobs) -> Result<(), String> {
+    let (pid, job) = match target.starts_with('%') {
+        true => {
+            let job = &jobs.list[jobs.find(target)?];
+            (-(job.pgid as libc::pid_t), Some(job))
+        }
+        false => match target.parse::<libc::pid_t>() {
+            Ok(pid) if pid > 0 => (pid, None),
+            _ => return Err(format!("Invalid pid: \"{target}\"")),
+        },
+    };
+
+    if unsafe { libc::kill(pid, signal) } < 0 {
+        return Err(io::Error::last_os_error().to_string());
+    }
+    // Остановленная задача должна проснуться, чтобы получить сигнал
+    if job.is_some_and(|job| matches!(job.state(), JobState::Stopped(_))) {
+        unsafe { libc::kill(pid, libc::SIGCONT) };
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_signal(_target: &str, _signal: i32, _jobs: &Jobs) -> Result<(), String> {
+    Err("signals are not supported on this platform".to_string())
 }
 
 /// Функционал fork/exec-команд
@@ -187,3 +1453,286 @@ fn start_subprocess(command: &str, args: &[&str]) -> Result<String, String> {
         Err(err) => Err(err.to_string()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(input: &str) -> Result<Vec<String>, String> {
+        let variable = |name: &str| match name {
+            "HOME" => Some("/home/user".to_string()),
+            "NAME" => Some("world".to_string()),
+            "?" => Some("3".to_string()),
+            _ => None,
+        };
+        let tokens = tokenize(input)?;
+        Ok(tokens
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Word(word) => word.expand(&variable),
+                token => Some(token.to_string()),
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_tokenize_quotes_and_escapes() {
+        assert_eq!(
+            words(r#"echo "a | b" 'c  d'"#).unwrap(),
+            ["echo", "a | b", "c  d"]
+        );
+        assert_eq!(
+            words(r#"a\ b "x\"y\\" 'no\esc'"#).unwrap(),
+            ["a b", "x\"y\\", "no\\esc"]
+        );
+        assert_eq!(
+            words(r#"pre"mid"'end' "" ''"#).unwrap(),
+            ["premidend", "", ""]
+        );
+        assert_eq!(
+            words("a|b&&c||d;e").unwrap(),
+            ["a", "'|'", "b", "'&&'", "c", "'||'", "d", "';'", "e"]
+        );
+        assert!(words("echo \"abc").is_err());
+        assert!(words("echo 'abc").is_err());
+        assert!(words("echo abc\\").is_err());
+    }
+
+    #[test]
+    fn test_tokenize_expansion() {
+        assert_eq!(
+            words("echo $NAME ${NAME}s \"$NAME!\" '$NAME'").unwrap(),
+            ["echo", "world", "worlds", "world!", "$NAME"]
+        );
+        assert_eq!(
+            words("echo $? $MISSING x$ \\$NAME").unwrap(),
+            ["echo", "3", "x$", "$NAME"]
+        );
+        assert_eq!(
+            words("cd ~ ~/src a~ '~'").unwrap(),
+            ["cd", "/home/user", "/home/user/src", "a~", "~"]
+        );
+        assert_eq!(words("echo \"$MISSING\"").unwrap(), ["echo", ""]);
+        assert!(words("echo ${NAME").is_err());
+        assert!(words("echo ${}").is_err());
+    }
+
+    #[test]
+    fn test_parse_ast() {
+        let script = parse("a 1 | b && c || d ; e").unwrap();
+        let command = |name: &str, args: &[&str]| SimpleCommand {
+            words: [name]
+                .iter()
+                .chain(args)
+                .map(|word| Word {
+                    parts: vec![WordPart::Literal(word.to_string())],
+                    quoted: false,
+                })
+                .collect(),
+        };
+        assert_eq!(
+            script,
+            vec![
+                AndOrList {
+                    first: Pipeline {
+                        commands: vec![command("a", &["1"]), command("b", &[])]
+                    },
+                    rest: vec![
+                        (
+                            Connector::And,
+                            Pipeline {
+                                commands: vec![command("c", &[])]
+                            }
+                        ),
+                        (
+                            Connector::Or,
+                            Pipeline {
+                                commands: vec![command("d", &[])]
+                            }
+                        ),
+                    ],
+                    background: false,
+                },
+                AndOrList {
+                    first: Pipeline {
+                        commands: vec![command("e", &[])]
+                    },
+                    rest: vec![],
+                    background: false,
+                },
+            ]
+        );
+
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("a ;").is_ok());
+        let script = parse("a & b | c &").unwrap();
+        assert_eq!(
+            script
+                .iter()
+                .map(|list| list.background)
+                .collect::<Vec<_>>(),
+            [true, true]
+        );
+        assert!(parse("a & b").is_ok());
+        for input in [
+            "| a",
+            "a |",
+            "a && || b",
+            "; a",
+            "a ;; b",
+            "& a",
+            "a && b &",
+            "a & & b",
+        ] {
+            assert!(parse(input).is_err(), "{input}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_status() {
+        let run = |input: &str| run_script(parse(input).unwrap(), 0, &mut Jobs::default());
+        assert_eq!(run("true"), Some(0));
+        assert_eq!(run("false"), Some(1));
+        assert_eq!(run("false | true"), Some(0));
+        assert_eq!(run("echo abc | grep -q abc"), Some(0));
+        assert_eq!(run("echo 'a | b' | grep -q 'a | b'"), Some(0));
+        assert_eq!(run("echo abc | grep -q xyz"), Some(1));
+        assert_eq!(run("no-such-command-t8"), Some(127));
+        assert_eq!(run("false && true"), Some(1));
+        assert_eq!(run("false || true"), Some(0));
+        assert_eq!(run("true || false"), Some(0));
+        assert_eq!(run("false; true"), Some(0));
+        assert_eq!(run("true; quit; false"), None);
+        // $? подставляется перед запуском каждого пайплайна
+        assert_eq!(run("false || test \"$?\" = 1"), Some(0));
+        assert_eq!(run("true && test \"$?\" = 0"), Some(0));
+        assert_eq!(run("$MISSING_T8"), Some(0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_background_jobs() {
+        let mut jobs = Jobs::default();
+        let mut run = |input: &str| run_script(parse(input).unwrap(), 0, &mut jobs);
+
+        // Фоновая задача не блокирует шелл и попадает в таблицу задач
+        assert_eq!(run("sleep 5 &"), Some(0));
+        assert_eq!(run("no-such-command-t8 &"), Some(127));
+        assert_eq!(run("sh -c 'exit 3' &"), Some(0));
+        assert_eq!(run("fg %7"), Some(1));
+        assert_eq!(run("fg 2"), Some(3));
+        assert_eq!(run("jobs | grep -q 'sleep 5'"), Some(0));
+        assert_eq!(jobs.list.len(), 1);
+        assert!(jobs.list().contains("[1]+  Running  sleep 5"));
+
+        // Остановка и продолжение через сигналы группе процессов
+        let pgid = jobs.list[0].pgid as libc::pid_t;
+        unsafe { libc::kill(-pgid, libc::SIGSTOP) };
+        jobs.list[0].poll(true);
+        assert_eq!(jobs.list[0].state(), JobState::Stopped(libc::SIGSTOP));
+        assert!(jobs.resume("%1").is_ok());
+        assert_eq!(jobs.list[0].state(), JobState::Running);
+
+        unsafe { libc::kill(-pgid, libc::SIGTERM) };
+        assert_eq!(jobs.foreground("").unwrap(), 128 + libc::SIGTERM);
+        assert!(jobs.list.is_empty());
+        assert!(jobs.foreground("").is_err());
+    }
+
+    #[test]
+    fn test_parse_stat() {
+        let stat = "42 (my (odd) name) S 1 42 42 0 -1 4194560 100 0 0 0 3 1 0 0 20 0 1 0 \
+                    12345 4096000 250 18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 0 0 0 0 0 0";
+        assert_eq!(
+            parse_stat(stat),
+            Some(Stat {
+                name: "my (odd) name",
+                state: 'S',
+                ppid: 1,
+                start_time: 12345,
+                rss: 250,
+            })
+        );
+        assert_eq!(parse_stat("42 (truncated) S 1"), None);
+
+        assert_eq!(format_cmdline(b"sleep\x0010\x00", "sleep"), "sleep 10");
+        assert_eq!(format_cmdline(b"", "kthreadd"), "[kthreadd]");
+    }
+
+    #[test]
+    fn test_ps_options() {
+        let process = |pid, ppid, state, rss, command: &str| ProcessInfo {
+            pid,
+            ppid,
+            state,
+            elapsed: 1000 - pid as u64,
+            rss,
+            command: command.to_string(),
+        };
+        let all = || {
+            vec![
+                process(3, 1, 'S', 300, "sleep 10"),
+                process(1, 0, 'S', 100, "init"),
+                process(2, 1, 'R', 200, "sleep 20"),
+            ]
+        };
+        let pids = |argument: &str| {
+            let mut processes = all();
+            PsOptions::parse(argument).unwrap().apply(&mut processes);
+            processes
+                .iter()
+                .map(|process| process.pid)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(pids(""), [1, 2, 3]);
+        assert_eq!(pids("-r"), [3, 2, 1]);
+        assert_eq!(pids("-s rss -r"), [3, 2, 1]);
+        assert_eq!(pids("-s time"), [3, 2, 1]);
+        assert_eq!(pids("-s cmd"), [1, 3, 2]);
+        assert_eq!(pids("-f sleep"), [2, 3]);
+        assert_eq!(pids("-p 1 -S S"), [3]);
+        for argument in ["-x", "-s", "-s size", "-p abc", "-S long"] {
+            assert!(PsOptions::parse(argument).is_err(), "{argument}");
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_read_processes() {
+        let processes = read_processes().unwrap();
+        let current = processes
+            .iter()
+            .find(|process| process.pid == process::id())
+            .unwrap();
+        assert_eq!(current.ppid, std::os::unix::process::parent_id());
+        assert!(current.rss > 0);
+        assert!(current.command.contains("t8"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_signals() {
+        assert_eq!(parse_signal("TERM"), Ok(libc::SIGTERM));
+        assert_eq!(parse_signal("sigkill"), Ok(libc::SIGKILL));
+        assert_eq!(parse_signal("9"), Ok(9));
+        assert_eq!(parse_signal("0"), Ok(0));
+        assert!(parse_signal("FOO").is_err());
+        assert!(parse_signal("1000").is_err());
+
+        let jobs = Jobs::default();
+        let mut child = process::Command::new("sleep").arg("10").spawn().unwrap();
+        let pid = child.id().to_string();
+        assert!(kill_process(&format!("-0 {pid}"), &jobs).is_ok());
+        assert!(kill_process(&format!("-s USR1 {pid}"), &jobs).is_ok());
+        let status = child.wait().unwrap();
+        assert_eq!(exit_code(status), 128 + libc::SIGUSR1);
+        assert!(kill_process(&format!("-0 {pid}"), &jobs).is_err());
+
+        assert!(kill_process("", &jobs).is_err());
+        assert!(kill_process("-9", &jobs).is_err());
+        assert!(kill_process("abc", &jobs).is_err());
+        assert!(kill_process("%1", &jobs).is_err());
+    }
+}
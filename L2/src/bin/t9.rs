@@ -3,6 +3,9 @@
 // reqwest + scraper + tokio
 
 // cargo run --bin t9 -- -l 4 https://rust-lang.org
+// cargo run --bin t9 -- -l 3 --no-parent --domains rust-lang.org https://doc.rust-lang.org/book/
+// cargo run --bin t9 -- -l 5 -j 16 --per-host 4 -w 100 --continue https://doc.rust-lang.org/book/
+// cargo run --bin t9 -- --single --continue https://static.rust-lang.org/dist/rust-1.81.0-x86_64-unknown-linux-gnu.tar.gz
 
 /*
 
@@ -16,6 +19,28 @@ Options:
           Определяет максимальную глубину вложенности страниц [default: 1]
   -o, --output-directory <OUTPUT_DIRECTORY>
           Директория для сохранения файлов [default: out]
+  -D, --domains <DOMAINS>
+          Дополнительные домены (вместе с поддоменами) через запятую, с которых можно загружать страницы и ресурсы
+      --no-parent
+          Не загружать страницы выше директории начальной страницы (ресурсы страниц загружаются)
+  -j, --jobs <JOBS>
+          Кол-во страниц, загружаемых одновременно [default: 8]
+      --per-host <PER_HOST>
+          Кол-во одновременных запросов к одному хосту [default: 2]
+  -w, --wait <WAIT>
+          Пауза между запросами к одному хосту, мсек (или Crawl-delay из robots.txt, если больше) [default: 0]
+  -t, --tries <TRIES>
+          Кол-во попыток при сетевых ошибках и ответах 5xx/429 [default: 3]
+      --retry-delay <RETRY_DELAY>
+          Пауза перед повторной попыткой, мсек (удваивается с каждой попыткой) [default: 500]
+      --ignore-robots
+          Не учитывать robots.txt
+  -c, --continue
+          Продолжить прерванную загрузку из сохраненного состояния (<OUTPUT_DIRECTORY>/.t9-state.json), с --single - докачать файл (HTTP Range)
+  -s, --single
+          Загрузить один файл без обхода ссылок (с индикатором прогресса)
+  -O, --output-document <OUTPUT_DOCUMENT>
+          Файл для --single (по умолчанию - <OUTPUT_DIRECTORY>/<имя из Content-Disposition или пути URL>)
   -h, --help
           Print help
 
@@ -55,15 +80,48 @@ https://rust-lang.org/governance/teams/lang
 
 */
 
+/* Пример вывода --single
+
+https://static.rust-lang.org/dist/rust-1.81.0-x86_64-unknown-linux-gnu.tar.gz
+[=============>                ]  46% 142.3 MiB / 309.1 MiB  11.8 MiB/s  ETA 0:14
+
+*/
+
 use clap::Parser;
-use reqwest::StatusCode;
-use scraper::{Html, Selector};
+use reqwest::{header, Client, StatusCode, Url};
+use scraper::{Html, Node, Selector};
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashSet, VecDeque},
-    env,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    io::{self, IsTerminal},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{OnceCell, Semaphore},
+    task::JoinSet,
+    time::Instant,
 };
 
-#[derive(Parser)]
+/// Файл состояния для --continue (в директории загрузки)
+const STATE_FILE: &str = ".t9-state.json";
+
+/// Как часто сохраняется состояние во время загрузки уровня
+const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// User-agent для запросов и robots.txt
+const USER_AGENT: &str = "t9";
+
+/// Как часто перерисовывается индикатор прогресса --single
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Ширина полосы индикатора прогресса
+const PROGRESS_WIDTH: usize = 30;
+
+#[derive(Parser, Clone)]
 struct Args {
     /// Страница для загрузки
     url: String,
@@ -75,139 +133,1771 @@ struct Args {
     /// Директория для сохранения файлов
     #[clap(short, long, default_value = "out")]
     output_directory: String,
+
+    /// Дополнительные домены (вместе с поддоменами) через запятую,
+    /// с которых можно загружать страницы и ресурсы
+    #[clap(short = 'D', long, value_delimiter = ',')]
+    domains: Vec<String>,
+
+    /// Не загружать страницы выше директории начальной страницы (ресурсы страниц загружаются)
+    #[clap(long)]
+    no_parent: bool,
+
+    /// Кол-во страниц, загружаемых одновременно
+    #[clap(short, long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: u32,
+
+    /// Кол-во одновременных запросов к одному хосту
+    #[clap(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..))]
+    per_host: u32,
+
+    /// Пауза между запросами к одному хосту, мсек (или Crawl-delay из robots.txt, если больше)
+    #[clap(short, long, default_value_t = 0)]
+    wait: u64,
+
+    /// Кол-во попыток при сетевых ошибках и ответах 5xx/429
+    #[clap(short, long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    tries: u32,
+
+    /// Пауза перед повторной попыткой, мсек (удваивается с каждой попыткой)
+    #[clap(long, default_value_t = 500)]
+    retry_delay: u64,
+
+    /// Не учитывать robots.txt
+    #[clap(long)]
+    ignore_robots: bool,
+
+    /// Продолжить прерванную загрузку из сохраненного состояния (<OUTPUT_DIRECTORY>/.t9-state.json),
+    /// с --single - докачать файл (HTTP Range)
+    #[clap(short = 'c', long = "continue")]
+    resume: bool,
+
+    /// Загрузить один файл без обхода ссылок (с индикатором прогресса)
+    #[clap(short, long)]
+    single: bool,
+
+    /// Файл для --single (по умолчанию - <OUTPUT_DIRECTORY>/<имя из Content-Disposition или пути URL>)
+    #[clap(short = 'O', long)]
+    output_document: Option<String>,
+}
+
+/// Вид ссылки на странице
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Link {
+    /// Страница - следующий уровень обхода
+    Page,
+    /// Ресурс (картинка, стиль, скрипт) - загружается вместе со страницей
+    Resource,
+}
+
+/// Что делать со ссылкой на странице
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Action {
+    /// Загружается: ссылка на локальный файл
+    Download,
+    /// Страница за пределами --level: абсолютная ссылка, но URL сохраняется для --continue
+    Later,
+    /// Не загружается: абсолютная ссылка
+    Skip,
+}
+
+/// Атрибуты со ссылками: селектор, атрибут, вид ссылки
+const LINK_ATTRIBUTES: [(&str, &str, Link); 4] = [
+    ("a[href]", "href", Link::Page),
+    ("area[href]", "href", Link::Page),
+    ("link[href]", "href", Link::Resource),
+    ("[src]", "src", Link::Resource),
+];
+
+/// Какие URL можно загружать (--domains, --no-parent)
+struct Scope {
+    hosts: Vec<String>,
+    /// Директория начальной страницы, если --no-parent
+    parent: Option<String>,
+}
+
+impl Scope {
+    fn new(start: &Url, args: &Args) -> Self {
+        let mut hosts: Vec<String> = start.host_str().map(str::to_string).into_iter().collect();
+        hosts.extend(
+            args.domains
+                .iter()
+                .map(|domain| domain.trim().trim_start_matches('.').to_lowercase())
+                .filter(|domain| !domain.is_empty()),
+        );
+        let parent = args.no_parent.then(|| {
+            let path = start.path();
+            path[..=path.rfind('/').unwrap_or_default()].to_string()
+        });
+        Scope { hosts, parent }
+    }
+
+    /// Домен из списка или его поддомен
+    fn allows_host(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        self.hosts
+            .iter()
+            .any(|domain| host == domain || host.ends_with(&format!(".{domain}")))
+    }
+
+    fn allows(&self, url: &Url, kind: Link) -> bool {
+        match kind {
+            Link::Page => {
+                self.allows_host(url)
+                    && self
+                        .parent
+                        .as_ref()
+                        .is_none_or(|parent| url.path().starts_with(parent.as_str()))
+            }
+            Link::Resource => self.allows_host(url),
+        }
+    }
+}
+
+/// Загруженный ресурс
+struct Resource {
+    bytes: Vec<u8>,
+    is_html: bool,
+}
+
+/// Страница после замены ссылок
+struct Page {
+    html: String,
+    /// Страницы следующего уровня
+    links: Vec<Url>,
+    resources: Vec<Url>,
+}
+
+/// Правила robots.txt для нашего user-agent
+#[derive(Debug, Default, PartialEq)]
+struct Robots {
+    /// (разрешено, шаблон пути)
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Разбор robots.txt: группа нашего user-agent, иначе группа "*".
+    /// Поддерживаются Allow, Disallow (с "*" и "$") и Crawl-delay
+    fn parse(text: &str) -> Self {
+        // Группы: (user-agent'ы, правила)
+        let mut groups: Vec<(Vec<String>, Robots)> = vec![];
+        let mut in_agents = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_lowercase(), value.trim());
+
+            if key == "user-agent" {
+                // Подряд идущие User-agent относятся к одной группе
+                if !in_agents {
+                    groups.push((vec![], Robots::default()));
+                }
+                in_agents = true;
+                groups.last_mut().unwrap().0.push(value.to_lowercase());
+                continue;
+            }
+            in_agents = false;
+            let Some((_, robots)) = groups.last_mut() else {
+                continue; // правила до первого User-agent
+            };
+            match key.as_str() {
+                "allow" if !value.is_empty() => robots.rules.push((true, value.to_string())),
+                "disallow" if !value.is_empty() => robots.rules.push((false, value.to_string())),
+                "crawl-delay" => {
+                    robots.crawl_delay = value.parse().ok().map(Duration::from_secs_f64);
+                }
+                _ => (),
+            }
+        }
+
+        let find = |agent: &str| {
+            groups
+                .iter()
+                .position(|(agents, _)| agents.iter().any(|a| a == agent))
+        };
+        match find(USER_AGENT).or_else(|| find("*")) {
+            Some(index) => groups.swap_remove(index).1,
+            None => Robots::default(),
+        }
+    }
+
+    /// Самое длинное подходящее правило, при равной длине - Allow
+    fn allows(&self, url: &Url) -> bool {
+        let path = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        };
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| robots_match(pattern, &path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+/// Совпадение пути с шаблоном robots.txt: префикс, "*" - любые символы, "$" - конец пути
+fn robots_match(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+
+    // Каждая следующая часть ищется как можно раньше, последняя при "$" - в конце пути
+    let parts: Vec<&str> = parts.collect();
+    let mut rest = rest;
+    for (index, part) in parts.iter().enumerate() {
+        if anchored && index + 1 == parts.len() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+/// Ограничения для одного хоста
+struct Host {
+    /// --per-host одновременных запросов
+    semaphore: Semaphore,
+    /// Время, раньше которого нельзя отправить следующий запрос (--wait, Crawl-delay)
+    next_request: tokio::sync::Mutex<Instant>,
+    robots: OnceCell<Robots>,
+}
+
+/// Итоги загрузки
+#[derive(Debug, Default)]
+struct Stats {
+    pages: usize,
+    resources: usize,
+    bytes: u64,
+    errors: usize,
+    retries: usize,
+    blocked: usize,
+}
+
+impl Stats {
+    fn report(&self, elapsed: Duration) -> String {
+        format!(
+            "Downloaded: {} pages, {} resources, {} in {:.1}s; {} errors, {} retries, {} blocked by robots.txt",
+            self.pages,
+            self.resources,
+            format_bytes(self.bytes),
+            elapsed.as_secs_f64(),
+            self.errors,
+            self.retries,
+            self.blocked
+        )
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{value:.1} {}", UNITS[unit]),
+    }
+}
+
+/// Состояние обхода для --continue
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct State {
+    /// Текущий уровень
+    level: usize,
+    /// Загруженные страницы и ресурсы
+    done: Vec<String>,
+    /// Страницы текущего уровня, которые еще не загружены
+    pending: Vec<String>,
+    /// Найденные страницы следующего уровня
+    next: Vec<String>,
+}
+
+impl State {
+    fn load(path: &Path) -> io::Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map(Some)
+                .map_err(io::Error::other),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Запись через временный файл, чтобы прерывание не испортило состояние
+    fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        let temporary = path.with_extension("tmp");
+        std::fs::write(
+            &temporary,
+            serde_json::to_string(self).map_err(io::Error::other)?,
+        )?;
+        std::fs::rename(temporary, path)
+    }
+}
+
+/// Общие данные задач загрузки
+struct Crawler {
+    args: Args,
+    scope: Scope,
+    client: Client,
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+    /// Ресурсы, загрузку которых уже начала какая-то задача
+    claimed: Mutex<HashSet<Url>>,
+    /// Загруженные страницы и ресурсы
+    done: Mutex<HashSet<Url>>,
+    /// Запрещенные robots.txt
+    blocked: Mutex<HashSet<Url>>,
+    stats: Mutex<Stats>,
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
+    let result = match args.single {
+        true => download_single(&args).await.map(|_| ()),
+        false => mirror(&args).await.map(|_| ()),
+    };
+    if let Err(err) = result {
+        eprintln!("t9: {err}");
+        std::process::exit(1);
+    }
+}
+
+/// Рекурсивная загрузка обходом в ширину: --level - кол-во уровней страниц (0 - без ограничения).
+/// Страницы уровня загружаются параллельно (--jobs, --per-host), следующий уровень - после.
+/// Ссылки на загружаемые страницы и ресурсы заменяются относительными путями к локальным файлам
+async fn mirror(args: &Args) -> Result<Stats, String> {
+    let started = Instant::now();
+    let start = Url::parse(&args.url).map_err(|err| format!("{}: {err}", args.url))?;
+    let start = without_fragment(start);
+    let state_path = Path::new(&args.output_directory).join(STATE_FILE);
+
+    // Начальное или сохраненное состояние
+    let parse_urls = |urls: Vec<String>| -> Vec<Url> {
+        urls.iter().filter_map(|url| Url::parse(url).ok()).collect()
+    };
+    let mut state = State {
+        pending: vec![start.to_string()],
+        ..State::default()
+    };
+    if args.resume {
+        let saved = State::load(&state_path).map_err(|err| format!("{STATE_FILE}: {err}"))?;
+        state = saved.unwrap_or(state);
+    }
+    let mut level = state.level;
+    let mut pending = parse_urls(state.pending);
+    let mut next = parse_urls(state.next);
 
-    let workdir = if args.output_directory.starts_with("/") {
-        args.output_directory
-    } else {
-        env::current_dir().unwrap().display().to_string() + "/" + &args.output_directory
+    let crawler = Arc::new(Crawler {
+        scope: Scope::new(&start, args),
+        client: Client::builder()
+            .user_agent(USER_AGENT)
+            .build()
+            .map_err(|err| err.to_string())?,
+        hosts: Mutex::new(HashMap::new()),
+        claimed: Mutex::new(HashSet::new()),
+        done: Mutex::new(parse_urls(state.done).into_iter().collect()),
+        blocked: Mutex::new(HashSet::new()),
+        stats: Mutex::new(Stats::default()),
+        args: args.clone(),
+    });
+    let save = |level: usize, pending: &[Url], next: &[Url]| {
+        let to_strings = |urls: &[Url]| urls.iter().map(Url::to_string).collect();
+        let done: Vec<Url> = crawler.done.lock().unwrap().iter().cloned().collect();
+        let state = State {
+            level,
+            done: to_strings(&done),
+            pending: to_strings(pending),
+            next: to_strings(next),
+        };
+        if let Err(err) = state.save(&state_path) {
+            eprintln!("t9: {STATE_FILE}: {err}");
+        }
     };
 
-    let mut level = 0;
-    let mut visited: HashSet<String> = HashSet::new();
-    let mut queue: VecDeque<String> = VecDeque::new();
+    let jobs = Arc::new(Semaphore::new(args.jobs as usize));
+    while !pending.is_empty() && (args.level == 0 || level < args.level) {
+        // Страницы следующего уровня загружаются, только если он будет
+        let follow = args.level == 0 || level + 1 < args.level;
+        // Ссылки на страницы этого и прошлых уровней тоже становятся локальными
+        let mut known = crawler.done.lock().unwrap().clone();
+        known.extend(pending.iter().cloned());
+        let known = Arc::new(known);
 
-    queue.push_back(args.url.clone());
-    while !queue.is_empty() {
-        let urls: Vec<String> = queue.drain(..).collect();
+        let mut tasks = JoinSet::new();
+        for url in pending.iter().cloned() {
+            let (crawler, jobs, known) = (crawler.clone(), jobs.clone(), known.clone());
+            tasks.spawn(async move {
+                let _permit = jobs.acquire_owned().await.expect("Semaphore closed");
+                let links = crawler.load_page(&url, follow, &known).await;
+                (url, links)
+            });
+        }
 
-        for url in urls {
-            if visited.contains(&url) {
-                continue;
+        // Результаты по мере готовности, состояние сохраняется периодически
+        let mut last_save = Instant::now();
+        while let Some(result) = tasks.join_next().await {
+            let (url, links) = result.map_err(|err| err.to_string())?;
+            pending.retain(|pending| *pending != url);
+            next.extend(links);
+            if last_save.elapsed() >= STATE_SAVE_INTERVAL {
+                save(level, &pending, &next);
+                last_save = Instant::now();
             }
-            println!("{}", url);
-            if let Some(mut content) = get_page(&url).await {
-                let dir = workdir.clone() + url.strip_prefix(&args.url).unwrap_or_default();
+        }
 
-                // Парсинг страницы
-                let (links, resources) = parse_page(&content);
+        // Следующий уровень без повторов и уже загруженных страниц
+        level += 1;
+        let done = crawler.done.lock().unwrap();
+        let mut seen = HashSet::new();
+        pending = next
+            .drain(..)
+            .filter(|url| !done.contains(url) && seen.insert(url.clone()))
+            .collect();
+        drop(done);
+        save(level, &pending, &next);
+    }
 
-                // Обработка ссылок
-                for original_link in links {
-                    // Фильтрация пустых ссылок и внешних ссылок
-                    if original_link.is_empty() || original_link == "/" {
-                        continue;
-                    }
-                    if !original_link.starts_with("/") && !original_link.starts_with(&args.url) {
-                        continue;
-                    }
+    let mut stats = std::mem::take(&mut *crawler.stats.lock().unwrap());
+    stats.blocked = crawler.blocked.lock().unwrap().len();
+    eprintln!("{}", stats.report(started.elapsed()));
+    Ok(stats)
+}
 
-                    // Формирование очереди скачивания
-                    let external_link = if original_link.starts_with("/") {
-                        args.url.clone() + original_link.as_str()
-                    } else {
-                        original_link.clone()
-                    };
-                    queue.push_back(external_link);
-
-                    // Изменение контента на локальную ссылку
-                    let local_link = workdir.clone() + &original_link + "/index.html";
-                    content = content.replace(&original_link, &local_link);
-                }
-
-                // Обработка ассетов
-                for original_resource in resources {
-                    if !original_resource.starts_with("/")
-                        && !original_resource.starts_with(&args.url)
-                    {
-                        continue;
-                    }
+impl Crawler {
+    /// Загрузка страницы и ее ресурсов. Результат - ссылки на страницы следующего уровня.
+    /// known - страницы, которые загружены или будут загружены на этом уровне
+    async fn load_page(
+        self: &Arc<Self>,
+        url: &Url,
+        follow: bool,
+        known: &HashSet<Url>,
+    ) -> Vec<Url> {
+        if self.done.lock().unwrap().contains(url) {
+            return vec![];
+        }
+        let Some(resource) = self.download(url).await else {
+            return vec![];
+        };
+        let path = local_path(url);
 
-                    let external_resource = if original_resource.starts_with("/") {
-                        args.url.clone() + original_resource.as_str()
-                    } else {
-                        original_resource.clone()
-                    };
+        let mut links = vec![];
+        let content = if resource.is_html {
+            let html = String::from_utf8_lossy(&resource.bytes);
+            self.load_link_robots(&html, url, &path).await;
 
-                    let resource_name = original_resource.split("/").last().unwrap();
-                    let resource_content = get_page(&external_resource).await.unwrap();
-                    save_data(&dir, &resource_name, &resource_content).await;
+            // Парсинг страницы и замена ссылок
+            let page = rewrite_page(&html, url, &path, |link, kind| {
+                if !self.scope.allows(link, kind) || !self.robots_allow_cached(link) {
+                    Action::Skip
+                } else if kind == Link::Resource || follow || known.contains(link) {
+                    Action::Download
+                } else {
+                    Action::Later
+                }
+            });
+            links = page.links;
 
-                    // Изменение контента на локальную ссылку
-                    let local_resource = "./".to_string() + &resource_name;
-                    content = content.replace(&original_resource, &local_resource);
+            // Ресурсы страницы загружаются параллельно (с ограничением --per-host)
+            let mut tasks = JoinSet::new();
+            for resource_url in page.resources {
+                if !self.claimed.lock().unwrap().insert(resource_url.clone()) {
+                    continue;
                 }
+                let crawler = self.clone();
+                tasks.spawn(async move { crawler.load_resource(resource_url).await });
+            }
+            while tasks.join_next().await.is_some() {}
+
+            page.html.into_bytes()
+        } else {
+            resource.bytes
+        };
+
+        let workdir = Path::new(&self.args.output_directory);
+        if let Err(err) = save_data(&workdir.join(path), &content).await {
+            eprintln!("{url}: {err}");
+            self.stats.lock().unwrap().errors += 1;
+            return links;
+        }
+        self.stats.lock().unwrap().pages += 1;
+        self.done.lock().unwrap().insert(url.clone());
+        links
+    }
 
-                save_data(&dir, "index.html", &content).await;
+    async fn load_resource(&self, url: Url) {
+        if self.done.lock().unwrap().contains(&url) {
+            return;
+        }
+        if let Some(resource) = self.download(&url).await {
+            let workdir = Path::new(&self.args.output_directory);
+            if let Err(err) = save_data(&workdir.join(local_path(&url)), &resource.bytes).await {
+                eprintln!("{url}: {err}");
+                self.stats.lock().unwrap().errors += 1;
+                return;
             }
-            visited.insert(url);
+            self.stats.lock().unwrap().resources += 1;
+            self.done.lock().unwrap().insert(url);
         }
+    }
 
-        level += 1;
-        if level == args.level {
-            break;
+    /// Загрузка с учетом robots.txt и повторами. Ошибки выводятся и учитываются в итогах
+    async fn download(&self, url: &Url) -> Option<Resource> {
+        let host = self.host(url);
+        if !self.args.ignore_robots && !self.robots(&host, url).await.allows(url) {
+            eprintln!("{url}: blocked by robots.txt");
+            self.blocked.lock().unwrap().insert(url.clone());
+            return None;
+        }
+        println!("{}", url);
+
+        let mut delay = Duration::from_millis(self.args.retry_delay);
+        for attempt in 1..=self.args.tries {
+            match self.get_resource(&host, url).await {
+                Ok(resource) => {
+                    self.stats.lock().unwrap().bytes += resource.bytes.len() as u64;
+                    return Some(resource);
+                }
+                Err((err, retry)) if retry && attempt < self.args.tries => {
+                    eprintln!("{url}: {err}, retrying in {}ms", delay.as_millis());
+                    self.stats.lock().unwrap().retries += 1;
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err((err, _)) => {
+                    eprintln!("{url}: {err}");
+                    self.stats.lock().unwrap().errors += 1;
+                    return None;
+                }
+            }
+        }
+        None
+    }
+
+    /// Ограничения хоста (создаются при первом обращении)
+    fn host(&self, url: &Url) -> Arc<Host> {
+        let key = format!("{}://{}", url.scheme(), url.authority());
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts
+            .entry(key)
+            .or_insert_with(|| {
+                Arc::new(Host {
+                    semaphore: Semaphore::new(self.args.per_host as usize),
+                    next_request: tokio::sync::Mutex::new(Instant::now()),
+                    robots: OnceCell::new(),
+                })
+            })
+            .clone()
+    }
+
+    /// robots.txt хоста (загружается один раз). Недоступный robots.txt ничего не запрещает
+    async fn robots<'a>(&self, host: &'a Host, url: &Url) -> &'a Robots {
+        host.robots
+            .get_or_init(|| async {
+                let mut robots_url = url.clone();
+                robots_url.set_path("/robots.txt");
+                robots_url.set_query(None);
+                match self.get_resource(host, &robots_url).await {
+                    Ok(resource) => Robots::parse(&String::from_utf8_lossy(&resource.bytes)),
+                    Err(_) => Robots::default(),
+                }
+            })
+            .await
+    }
+
+    /// Загрузка robots.txt всех хостов, на которые ссылается страница (в пределах --domains).
+    /// Без него запрещенные ссылки стали бы локальными и остались бы незагруженными
+    async fn load_link_robots(&self, html: &str, url: &Url, path: &Path) {
+        if self.args.ignore_robots {
+            return;
+        }
+        let hosts = RefCell::new(HashMap::new());
+        rewrite_page(html, url, path, |link, kind| {
+            if self.scope.allows(link, kind) {
+                let key = format!("{}://{}", link.scheme(), link.authority());
+                hosts
+                    .borrow_mut()
+                    .entry(key)
+                    .or_insert_with(|| link.clone());
+            }
+            Action::Skip
+        });
+        for link in hosts.into_inner().into_values() {
+            self.robots(&self.host(&link), &link).await;
+        }
+    }
+
+    /// Проверка robots.txt без ожидания (robots.txt хостов ссылок загружен заранее):
+    /// если он еще не загружен, ссылка считается разрешенной
+    fn robots_allow_cached(&self, url: &Url) -> bool {
+        if self.args.ignore_robots {
+            return true;
+        }
+        let allowed = self
+            .host(url)
+            .robots
+            .get()
+            .is_none_or(|robots| robots.allows(url));
+        if !allowed {
+            self.blocked.lock().unwrap().insert(url.clone());
+        }
+        allowed
+    }
+
+    /// Один запрос с ограничениями хоста: не больше --per-host одновременно
+    /// и не чаще --wait (Crawl-delay). Ошибка - (текст, стоит ли повторить)
+    async fn get_resource(&self, host: &Host, url: &Url) -> Result<Resource, (String, bool)> {
+        let _permit = host.semaphore.acquire().await.expect("Semaphore closed");
+        let delay = Duration::from_millis(self.args.wait).max(
+            host.robots
+                .get()
+                .and_then(|robots| robots.crawl_delay)
+                .unwrap_or_default(),
+        );
+        {
+            let mut next_request = host.next_request.lock().await;
+            tokio::time::sleep_until(*next_request).await;
+            *next_request = Instant::now() + delay;
         }
+
+        let response = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|err| (err.to_string(), true))?;
+        let status = response.status();
+        if status != StatusCode::OK {
+            let retry = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+            return Err((status.to_string(), retry));
+        }
+
+        let is_html = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("text/html") || value.contains("xhtml"));
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|err| (err.to_string(), true))?;
+        Ok(Resource {
+            bytes: bytes.to_vec(),
+            is_html,
+        })
     }
 }
 
-/// Получение страницы
-async fn get_page(url: &String) -> Option<String> {
-    if let Ok(response) = reqwest::get(url).await {
-        if response.status() == StatusCode::OK {
-            if let Ok(content) = response.text().await {
-                return Some(content);
+/// Загрузка одного файла (--single): тело ответа пишется на диск по частям, без буферизации в памяти.
+/// С --continue существующий файл докачивается запросом Range. Результат - путь к файлу
+async fn download_single(args: &Args) -> Result<PathBuf, String> {
+    let started = Instant::now();
+    let url = Url::parse(&args.url).map_err(|err| format!("{}: {err}", args.url))?;
+    let client = Client::builder()
+        .user_agent(USER_AGENT)
+        .build()
+        .map_err(|err| err.to_string())?;
+    println!("{url}");
+
+    // Чтобы докачать файл, его имя нужно знать до загрузки: оно берется из ответа на HEAD
+    let mut path = args.output_document.as_ref().map(PathBuf::from);
+    if path.is_none() && args.resume {
+        let response = client.head(url.clone()).send().await.ok();
+        let name = match response.filter(|response| response.status().is_success()) {
+            Some(response) => file_name(&response),
+            None => url_file_name(&url),
+        };
+        path = Some(Path::new(&args.output_directory).join(name));
+    }
+    let mut offset = match &path {
+        Some(path) if args.resume => std::fs::metadata(path).map_or(0, |metadata| metadata.len()),
+        _ => 0,
+    };
+
+    let mut request = client.get(url.clone());
+    if offset > 0 {
+        request = request.header(header::RANGE, format!("bytes={offset}-"));
+    }
+    let mut response = request
+        .send()
+        .await
+        .map_err(|err| format!("{url}: {err}"))?;
+    let path = path.unwrap_or_else(|| Path::new(&args.output_directory).join(file_name(&response)));
+    let content_range = response
+        .headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_content_range);
+
+    // Ожидаемый размер всего файла
+    let total = match response.status() {
+        StatusCode::PARTIAL_CONTENT => match content_range {
+            Some((Some(start), total)) if start == offset => {
+                total.or(response.content_length().map(|length| offset + length))
+            }
+            _ => return Err(format!("{url}: unexpected Content-Range")),
+        },
+        StatusCode::OK => {
+            if offset > 0 {
+                eprintln!("{url}: server doesn't support ranges, downloading from the start");
+                offset = 0;
             }
+            response.content_length()
         }
+        // Докачивать нечего
+        StatusCode::RANGE_NOT_SATISFIABLE
+            if offset > 0 && content_range.is_some_and(|(_, total)| total == Some(offset)) =>
+        {
+            eprintln!("{}: already fully downloaded", path.display());
+            return Ok(path);
+        }
+        status => return Err(format!("{url}: {status}")),
+    };
+
+    if let Some(directory) = path.parent() {
+        tokio::fs::create_dir_all(directory)
+            .await
+            .map_err(|err| format!("{}: {err}", directory.display()))?;
     }
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(&path)
+        .await
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+
+    // Ошибка соединения не удаляет загруженную часть: ее можно докачать с --continue
+    let mut progress = Progress::new(offset, total);
+    let mut error = None;
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                if let Err(err) = file.write_all(&chunk).await {
+                    error = Some(format!("{}: {err}", path.display()));
+                    break;
+                }
+                progress.advance(chunk.len());
+            }
+            Ok(None) => break,
+            Err(err) => {
+                error = Some(format!("{url}: {err}"));
+                break;
+            }
+        }
+    }
+    file.flush()
+        .await
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+    progress.finish();
 
-    None
+    if let Some(total) = total.filter(|total| *total != progress.done) {
+        return Err(format!(
+            "{}: incomplete download: {} of {} bytes (resume with --continue)",
+            path.display(),
+            progress.done,
+            total
+        ));
+    }
+    if let Some(error) = error {
+        return Err(error);
+    }
+    eprintln!(
+        "Saved: {} ({}) in {:.1}s",
+        path.display(),
+        format_bytes(progress.done),
+        started.elapsed().as_secs_f64()
+    );
+    Ok(path)
 }
 
-/// Парсинг ссылок и ассетов
-fn parse_page(page: &String) -> (HashSet<String>, HashSet<String>) {
-    let html = Html::parse_document(page);
+/// Индикатор прогресса --single в stderr: полоса, скорость и оставшееся время.
+/// Перерисовывается, только если stderr - терминал
+struct Progress {
+    /// Байт в файле (вместе с загруженными ранее)
+    done: u64,
+    /// Размер файла, если известен
+    total: Option<u64>,
+    /// Размер файла в начале загрузки (для скорости)
+    offset: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+    terminal: bool,
+}
+
+impl Progress {
+    fn new(offset: u64, total: Option<u64>) -> Self {
+        Self {
+            done: offset,
+            total,
+            offset,
+            started: Instant::now(),
+            drawn: None,
+            terminal: io::stderr().is_terminal(),
+        }
+    }
 
-    // Парсинг ссылок
-    let mut links = HashSet::new();
-    let links_selector = Selector::parse("a[href]").unwrap();
-    for element in html.select(&links_selector) {
-        if let Some(link) = element.value().attr("href") {
-            links.insert(link.to_string());
+    fn advance(&mut self, bytes: usize) {
+        self.done += bytes as u64;
+        if self.terminal
+            && self
+                .drawn
+                .is_none_or(|drawn| drawn.elapsed() >= PROGRESS_INTERVAL)
+        {
+            self.draw();
         }
     }
 
-    // Парсинг ресурсов
-    let mut resources = HashSet::new();
-    let resources_selector = Selector::parse("[src]").unwrap();
-    for element in html.select(&resources_selector) {
-        if let Some(resource) = element.value().attr("src") {
-            resources.insert(resource.to_string());
+    fn finish(&mut self) {
+        if self.terminal {
+            self.draw();
+            eprintln!();
         }
     }
 
-    (links, resources)
+    fn draw(&mut self) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = match elapsed > 0.0 {
+            true => (self.done - self.offset) as f64 / elapsed,
+            false => 0.0,
+        };
+        eprint!("\r{}\x1b[K", format_progress(self.done, self.total, rate));
+        self.drawn = Some(Instant::now());
+    }
 }
 
-async fn save_data(directory: &str, name: &str, content: &str) {
-    tokio::fs::create_dir_all(&directory)
-        .await
-        .expect("Couldn't create output directory");
+/// Строка индикатора: rate - скорость, байт/сек
+fn format_progress(done: u64, total: Option<u64>, rate: f64) -> String {
+    let rate_text = format!("{}/s", format_bytes(rate as u64));
+    let Some(total) = total.filter(|total| *total > 0) else {
+        return format!("{}  {rate_text}", format_bytes(done));
+    };
 
-    tokio::fs::write(format!("{directory}/{name}"), content)
-        .await
-        .expect("Couldn't write to the file")
+    let done = done.min(total);
+    let filled = (done as u128 * PROGRESS_WIDTH as u128 / total as u128) as usize;
+    let mut bar = "=".repeat(filled);
+    if filled < PROGRESS_WIDTH {
+        bar.push('>');
+    }
+    let eta = match rate > 0.0 {
+        true => format_eta(((total - done) as f64 / rate).ceil() as u64),
+        false => "--:--".to_string(),
+    };
+    format!(
+        "[{bar:<width$}] {:>3}% {} / {}  {rate_text}  ETA {eta}",
+        done * 100 / total,
+        format_bytes(done),
+        format_bytes(total),
+        width = PROGRESS_WIDTH
+    )
+}
+
+/// Оставшееся время: "м:сс" или "ч:мм:сс"
+fn format_eta(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match hours {
+        0 => format!("{minutes}:{seconds:02}"),
+        _ => format!("{hours}:{minutes:02}:{seconds:02}"),
+    }
+}
+
+/// Content-Range: "bytes <start>-<end>/<total>" или "bytes */<total>". Результат - (start, total)
+fn parse_content_range(value: &str) -> Option<(Option<u64>, Option<u64>)> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let start = match range.trim() {
+        "*" => None,
+        range => Some(range.split_once('-')?.0.parse().ok()?),
+    };
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    Some((start, total))
+}
+
+/// Замена ссылок в атрибутах HTML. action(ссылка, вид) - что делать со ссылкой:
+/// загружаемые ссылки становятся относительными путями к локальным файлам, остальные - абсолютными URL.
+/// path - локальный путь самой страницы
+fn rewrite_page(
+    content: &str,
+    url: &Url,
+    path: &Path,
+    action: impl Fn(&Url, Link) -> Action,
+) -> Page {
+    let mut html = Html::parse_document(content);
+    let mut page = Page {
+        html: String::new(),
+        links: vec![],
+        resources: vec![],
+    };
+
+    // <base href> меняет базу относительных ссылок. Локальные ссылки от него не зависят
+    let base_selector = Selector::parse("base[href]").unwrap();
+    let mut base = url.clone();
+    let mut base_nodes = vec![];
+    for element in html.select(&base_selector) {
+        if let Some(href) = element
+            .value()
+            .attr("href")
+            .and_then(|href| url.join(href).ok())
+        {
+            base = href;
+        }
+        base_nodes.push(element.id());
+    }
+
+    // Новые значения атрибутов
+    let mut changes = vec![];
+    for (selector, attribute, kind) in LINK_ATTRIBUTES {
+        let selector = Selector::parse(selector).unwrap();
+        for element in html.select(&selector) {
+            let Some(value) = element.value().attr(attribute) else {
+                continue;
+            };
+            let Some(link) = resolve_link(&base, value) else {
+                continue;
+            };
+            let target = without_fragment(link.clone());
+
+            let new_value = match action(&target, kind) {
+                Action::Download => {
+                    let mut local = relative_link(path, &local_path(&target));
+                    if let Some(fragment) = link.fragment() {
+                        local = format!("{local}#{fragment}");
+                    }
+                    match kind {
+                        Link::Page => page.links.push(target),
+                        Link::Resource => page.resources.push(target),
+                    }
+                    local
+                }
+                Action::Later => {
+                    page.links.push(target);
+                    link.to_string()
+                }
+                Action::Skip => link.to_string(),
+            };
+            changes.push((element.id(), attribute, new_value));
+        }
+    }
+
+    // Изменение DOM: только значения найденных атрибутов
+    for (id, attribute, new_value) in changes {
+        let Some(mut node) = html.tree.get_mut(id) else {
+            continue;
+        };
+        if let Node::Element(element) = node.value() {
+            for (name, value) in element.attrs.iter_mut() {
+                if &*name.local == attribute {
+                    *value = new_value.as_str().into();
+                }
+            }
+        }
+    }
+    for id in base_nodes {
+        if let Some(mut node) = html.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+
+    page.html = html.html();
+    page
+}
+
+/// Абсолютный URL ссылки. None - ссылка на эту же страницу ("#...") или не HTTP (mailto:, data:, ...)
+fn resolve_link(base: &Url, value: &str) -> Option<Url> {
+    let value = value.trim();
+    if value.is_empty() || value.starts_with('#') {
+        return None;
+    }
+    let link = base.join(value).ok()?;
+    matches!(link.scheme(), "http" | "https").then_some(link)
+}
+
+/// Нормализованный URL для загрузки и дедупликации (фрагмент не влияет на ресурс)
+fn without_fragment(mut url: Url) -> Url {
+    url.set_fragment(None);
+    url
+}
+
+/// Локальный путь файла для URL: <host>[_port]/<path>.
+/// Директории и пути без расширения - <path>/index.html, запрос - "@query" перед расширением
+fn local_path(url: &Url) -> PathBuf {
+    let host = url.host_str().unwrap_or("unknown");
+    let mut path = PathBuf::from(match url.port() {
+        Some(port) => sanitize(&format!("{host}_{port}")),
+        None => sanitize(host),
+    });
+
+    let mut segments: Vec<&str> = url
+        .path_segments()
+        .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+        .unwrap_or_default();
+    let is_directory =
+        url.path().ends_with('/') || segments.last().is_none_or(|segment| !segment.contains('.'));
+    let name = match is_directory {
+        true => "index.html",
+        false => segments.pop().unwrap(),
+    };
+    for segment in segments {
+        path.push(sanitize(segment));
+    }
+
+    let name = match (url.query(), name.rfind('.')) {
+        (Some(query), Some(dot)) => format!("{}@{query}{}", &name[..dot], &name[dot..]),
+        (Some(query), None) => format!("{name}@{query}"),
+        (None, _) => name.to_string(),
+    };
+    path.push(sanitize(&name));
+    path
+}
+
+/// Имя файла для --single: из Content-Disposition, иначе из пути URL (после перенаправлений)
+fn file_name(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get(header::CONTENT_DISPOSITION)
+        .and_then(|value| content_disposition_file_name(&String::from_utf8_lossy(value.as_bytes())))
+        .unwrap_or_else(|| url_file_name(response.url()))
+}
+
+/// Имя файла из Content-Disposition: filename* (RFC 6266, "UTF-8''<percent-encoded>") важнее filename
+fn content_disposition_file_name(value: &str) -> Option<String> {
+    let mut name = None;
+    for (key, value) in header_parameters(value) {
+        match key.as_str() {
+            "filename*" => {
+                let mut parts = value.splitn(3, '\'');
+                let (charset, encoded) = (parts.next()?, parts.nth(1));
+                if let Some(encoded) = encoded.filter(|_| charset.eq_ignore_ascii_case("utf-8")) {
+                    if let Some(extended) = safe_file_name(&percent_decode(encoded)) {
+                        return Some(extended);
+                    }
+                }
+            }
+            "filename" if name.is_none() => name = safe_file_name(&value),
+            _ => {}
+        }
+    }
+    name
+}
+
+/// Параметры заголовка "value; key=token; key="quoted \"string\"" (ключи в нижнем регистре)
+fn header_parameters(value: &str) -> Vec<(String, String)> {
+    let mut parameters = vec![];
+    let mut current = String::new();
+    let (mut quoted, mut escaped) = (false, false);
+    for ch in value.chars().chain([';']) {
+        match ch {
+            _ if escaped => {
+                current.push(ch);
+                escaped = false;
+            }
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                if let Some((key, value)) = current.split_once('=') {
+                    parameters.push((key.trim().to_ascii_lowercase(), value.trim().to_string()));
+                }
+                current.clear();
+            }
+            ch => current.push(ch),
+        }
+    }
+    parameters
+}
+
+/// Имя файла из последнего сегмента пути URL (декодированного), для "/" - index.html
+fn url_file_name(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.rfind(|segment| !segment.is_empty()))
+        .and_then(|segment| safe_file_name(&percent_decode(segment)))
+        .unwrap_or_else(|| "index.html".to_string())
+}
+
+/// Имя без пути (имя от сервера не должно указывать за пределы директории загрузки)
+fn safe_file_name(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next()?.trim();
+    match name {
+        "" | "." | ".." => None,
+        name => Some(sanitize(name)),
+    }
+}
+
+/// Декодирование "%XX" (некорректный UTF-8 заменяется)
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Символы, недопустимые в именах файлов (Windows), заменяются на "_"
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|ch| match ch {
+            '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            ch if ch.is_control() => '_',
+            ch => ch,
+        })
+        .collect()
+}
+
+/// Относительная ссылка из файла from на файл to (оба пути относительно директории загрузки)
+fn relative_link(from: &Path, to: &Path) -> String {
+    let from: Vec<Component> = from
+        .parent()
+        .unwrap_or(Path::new(""))
+        .components()
+        .collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(
+        to[common..]
+            .iter()
+            .map(|part| part.as_os_str().to_string_lossy().into_owned()),
+    );
+    // "%" в имени файла (из URL) не должен декодироваться браузером
+    parts.join("/").replace('%', "%25")
+}
+
+/// Запись файла вместе с директориями. Ошибка возможна, например,
+/// если файл и директория получают один путь (/v1.0 и /v1.0/x/)
+async fn save_data(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(directory) = path.parent() {
+        tokio::fs::create_dir_all(directory).await?;
+    }
+    tokio::fs::write(path, content).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        extract::State as Extract,
+        http::{HeaderMap, StatusCode, Uri},
+        response::{IntoResponse, Response},
+        Router,
+    };
+    use std::{fs, sync::atomic::AtomicUsize, sync::atomic::Ordering};
+
+    const LOGO: &[u8] = &[0x89, b'P', b'N', b'G', 0xff, 0x00, 0xfe, b'\n'];
+
+    /// Счетчики тестового сервера
+    #[derive(Default)]
+    struct Fixture {
+        /// Кол-во запросов по путям
+        requests: Mutex<HashMap<String, usize>>,
+        active: AtomicUsize,
+        max_active: AtomicUsize,
+        /// Заголовки Range запросов
+        ranges: Mutex<Vec<String>>,
+    }
+
+    impl Fixture {
+        fn requests(&self, path: &str) -> usize {
+            self.requests
+                .lock()
+                .unwrap()
+                .get(path)
+                .copied()
+                .unwrap_or_default()
+        }
+    }
+
+    /// Файл для --single
+    fn file_data() -> Vec<u8> {
+        (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    /// Локальный HTTP-сервер с тестовым сайтом. Результат - порт и счетчики
+    async fn serve_fixture() -> (u16, Arc<Fixture>) {
+        async fn handler(
+            Extract(fixture): Extract<Arc<Fixture>>,
+            uri: Uri,
+            headers: HeaderMap,
+        ) -> Response {
+            let path = uri.path().to_string();
+            let count = {
+                let mut requests = fixture.requests.lock().unwrap();
+                let count = requests.entry(path.clone()).or_default();
+                *count += 1;
+                *count
+            };
+            if let Some(range) = headers.get("range").and_then(|range| range.to_str().ok()) {
+                fixture.ranges.lock().unwrap().push(range.to_string());
+            }
+
+            // Одновременные запросы (ответ задерживается, чтобы они пересекались)
+            let active = fixture.active.fetch_add(1, Ordering::SeqCst) + 1;
+            fixture.max_active.fetch_max(active, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            fixture.active.fetch_sub(1, Ordering::SeqCst);
+
+            // Порт сервера - из заголовка Host
+            let host = headers.get("host").and_then(|host| host.to_str().ok());
+            let port = host
+                .and_then(|host| host.rsplit(':').next())
+                .unwrap_or_default();
+            let html = |body: String| {
+                ([("content-type", "text/html; charset=utf-8")], body).into_response()
+            };
+            match path.as_str() {
+                "/robots.txt" => {
+                    "User-agent: *\nDisallow: /docs/private\nAllow: /docs/private-ok$\n"
+                        .into_response()
+                }
+                "/docs/" => html(format!(
+                    r##"<!DOCTYPE html>
+<html><head><link rel="stylesheet" href="../static/style.css"></head><body>
+<a href="page.html#top">Page</a> <a href="./sub/">Sub</a> <a href="/docs/page.html">Dup</a>
+<a href="../about">About</a> <a href="http://localhost:{port}/docs/other.html">Other</a>
+<a href="mailto:user@example.com">Mail</a> <a href="#local">Anchor</a>
+<a href="private.html">Private</a> <a href="private-ok">Allowed</a>
+<img src="/static/logo.png?v=2"><script src="/static/flaky.js"></script>
+<p>page.html ../about text</p>
+</body></html>"##
+                )),
+                "/docs/page.html" => html(r#"<a href="./">Back</a>"#.to_string()),
+                // Файл /api/v1.0 и директория /api/v1.0/x/ - один локальный путь
+                "/api/" => html(format!(
+                    r#"<a href="v1.0">v1.0</a> <a href="v1.0/x/">x</a>
+<a href="http://localhost:{port}/docs/private.html">Private</a>"#
+                )),
+                "/api/v1.0" | "/api/v1.0/x/" => html(path),
+                "/docs/sub/" => html(
+                    r#"<a href="../page.html">Up</a> <a href="deeper.html">Deeper</a>"#.to_string(),
+                ),
+                "/docs/other.html" | "/docs/private.html" | "/docs/private-ok" | "/about" => {
+                    html(path)
+                }
+                "/static/style.css" => ([("content-type", "text/css")], "p {}").into_response(),
+                "/static/logo.png" => ([("content-type", "image/png")], LOGO).into_response(),
+                // Первый запрос - ошибка сервера
+                "/static/flaky.js" if count == 1 => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+                "/static/flaky.js" => "flaky()".into_response(),
+                // Поддерживает докачку
+                "/files/data.bin" => {
+                    let data = file_data();
+                    let start = headers
+                        .get("range")
+                        .and_then(|range| range.to_str().ok())
+                        .and_then(|range| range.strip_prefix("bytes=")?.strip_suffix('-'))
+                        .and_then(|start| start.parse::<usize>().ok());
+                    match start {
+                        None => data.into_response(),
+                        Some(start) if start >= data.len() => (
+                            StatusCode::RANGE_NOT_SATISFIABLE,
+                            [("content-range", format!("bytes */{}", data.len()))],
+                        )
+                            .into_response(),
+                        Some(start) => (
+                            StatusCode::PARTIAL_CONTENT,
+                            [(
+                                "content-range",
+                                format!("bytes {start}-{}/{}", data.len() - 1, data.len()),
+                            )],
+                            data[start..].to_vec(),
+                        )
+                            .into_response(),
+                    }
+                }
+                // Не поддерживает докачку
+                "/files/plain.bin" => file_data().into_response(),
+                "/files/report" => (
+                    [(
+                        "content-disposition",
+                        "attachment; filename=\"report.txt\"; \
+                         filename*=UTF-8''%D0%BE%D1%82%D1%87%D0%B5%D1%82.txt",
+                    )],
+                    "report",
+                )
+                    .into_response(),
+                _ => StatusCode::NOT_FOUND.into_response(),
+            }
+        }
+
+        let fixture = Arc::new(Fixture::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let router = Router::new().fallback(handler).with_state(fixture.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        (port, fixture)
+    }
+
+    /// Запуск t9 против тестового сайта. Результат - директория загрузки и порт
+    async fn run_mirror(options: &[&str]) -> (tempfile::TempDir, u16) {
+        let (port, _) = serve_fixture().await;
+        let directory = tempfile::tempdir().unwrap();
+        mirror_fixture(port, directory.path(), options).await;
+        (directory, port)
+    }
+
+    async fn mirror_fixture(port: u16, directory: &Path, options: &[&str]) -> Stats {
+        let url = format!("http://127.0.0.1:{port}/docs/");
+        let output = directory.to_str().unwrap();
+        let mut argv = vec!["t9", url.as_str(), "-o", output, "--retry-delay", "10"];
+        argv.extend(options);
+        mirror(&Args::parse_from(argv)).await.unwrap()
+    }
+
+    /// Сервер, обрывающий соединение на середине тела ответа. Результат - порт
+    async fn serve_truncated() -> u16 {
+        use tokio::io::AsyncReadExt;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0; 1024];
+                let _ = socket.read(&mut request).await;
+                let _ = socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n")
+                    .await;
+                let _ = socket.write_all(&[b'x'; 40]).await;
+            }
+        });
+        port
+    }
+
+    async fn download_fixture(
+        port: u16,
+        directory: &Path,
+        path: &str,
+        options: &[&str],
+    ) -> Result<PathBuf, String> {
+        let url = format!("http://127.0.0.1:{port}{path}");
+        let output = directory.to_str().unwrap();
+        let mut argv = vec!["t9", url.as_str(), "--single", "-o", output];
+        argv.extend(options);
+        download_single(&Args::parse_from(argv)).await
+    }
+
+    #[test]
+    fn test_local_path() {
+        let path = |url: &str| local_path(&Url::parse(url).unwrap());
+        assert_eq!(path("http://a.com"), Path::new("a.com/index.html"));
+        assert_eq!(path("http://a.com/x/"), Path::new("a.com/x/index.html"));
+        assert_eq!(
+            path("http://a.com/x/page"),
+            Path::new("a.com/x/page/index.html")
+        );
+        assert_eq!(path("http://a.com/x/../y.png"), Path::new("a.com/y.png"));
+        assert_eq!(
+            path("http://a.com:8080/a.js?v=2"),
+            Path::new("a.com_8080/a@v=2.js")
+        );
+        assert_eq!(
+            path("http://a.com/?q=a/b"),
+            Path::new("a.com/index@q=a_b.html")
+        );
+    }
+
+    #[test]
+    fn test_relative_link() {
+        let link = |from: &str, to: &str| relative_link(Path::new(from), Path::new(to));
+        assert_eq!(link("a/index.html", "a/index.html"), "index.html");
+        assert_eq!(link("a/b/index.html", "a/c/x.png"), "../c/x.png");
+        assert_eq!(link("a/index.html", "a/b/c/index.html"), "b/c/index.html");
+        assert_eq!(link("a/index.html", "b/x%20y.png"), "../b/x%2520y.png");
+    }
+
+    #[test]
+    fn test_resolve_link() {
+        let base = Url::parse("http://a.com/docs/index.html?x=1").unwrap();
+        let link = |value: &str| resolve_link(&base, value).map(|url| url.to_string());
+        assert_eq!(link("page.html").unwrap(), "http://a.com/docs/page.html");
+        assert_eq!(link("../a/./b").unwrap(), "http://a.com/a/b");
+        assert_eq!(
+            link("?y=2#top").unwrap(),
+            "http://a.com/docs/index.html?y=2#top"
+        );
+        assert_eq!(link("//B.com/x").unwrap(), "http://b.com/x");
+        assert_eq!(link("#top"), None);
+        assert_eq!(link("mailto:user@example.com"), None);
+        assert_eq!(link("javascript:void(0)"), None);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_rewrites_links() {
+        let (directory, port) = run_mirror(&["-l", "2"]).await;
+        let root = directory.path().join(format!("127.0.0.1_{port}"));
+        let read = |path: &str| fs::read_to_string(root.join(path)).unwrap();
+
+        // Страницы двух уровней, ресурсы и двоичные данные без искажений
+        let index = read("docs/index.html");
+        assert!(root.join("docs/page.html").exists());
+        assert!(root.join("docs/sub/index.html").exists());
+        assert!(root.join("about/index.html").exists());
+        assert_eq!(read("static/style.css"), "p {}");
+        assert_eq!(fs::read(root.join("static/logo@v=2.png")).unwrap(), LOGO);
+
+        // Ссылки заменены в атрибутах, текст страницы не изменен
+        assert!(index.contains(r#"href="page.html#top""#));
+        assert!(index.contains(r#"href="sub/index.html""#));
+        assert!(index.contains(r#"href="../about/index.html""#));
+        assert!(index.contains(r#"href="../static/style.css""#));
+        assert!(index.contains(r#"src="../static/logo@v=2.png""#));
+        assert!(index.contains(r#"href="mailto:user@example.com""#));
+        assert!(index.contains(r##"href="#local""##));
+        assert!(index.contains("<p>page.html ../about text</p>"));
+
+        // Другой домен не загружается, ссылка на него - абсолютная
+        assert!(index.contains(&format!(
+            r#"href="http://localhost:{port}/docs/other.html""#
+        )));
+        assert!(!directory.path().join(format!("localhost_{port}")).exists());
+
+        // Ссылки третьего уровня не загружаются, ссылки на загруженные страницы - локальные
+        let sub = read("docs/sub/index.html");
+        assert!(sub.contains(r#"href="../page.html""#));
+        assert!(sub.contains(&format!(
+            r#"href="http://127.0.0.1:{port}/docs/sub/deeper.html""#
+        )));
+        assert!(!root.join("docs/sub/deeper.html").exists());
+    }
+
+    #[tokio::test]
+    async fn test_mirror_scope() {
+        let (directory, port) =
+            run_mirror(&["-l", "2", "--no-parent", "--domains", "localhost"]).await;
+        let root = directory.path().join(format!("127.0.0.1_{port}"));
+        let index = fs::read_to_string(root.join("docs/index.html")).unwrap();
+
+        // --no-parent: страница выше не загружается, ресурсы - загружаются
+        assert!(!root.join("about").exists());
+        assert!(index.contains(&format!(r#"href="http://127.0.0.1:{port}/about""#)));
+        assert!(root.join("static/style.css").exists());
+
+        // --domains: страница с другого домена загружена
+        let other = format!("localhost_{port}/docs/other.html");
+        assert!(directory.path().join(&other).exists());
+        assert!(index.contains(&format!(r#"href="../../{other}""#)));
+    }
+
+    #[tokio::test]
+    async fn test_mirror_save_error() {
+        let (port, _) = serve_fixture().await;
+        let directory = tempfile::tempdir().unwrap();
+        let url = format!("http://127.0.0.1:{port}/api/");
+        let output = directory.path().to_str().unwrap();
+        let args = Args::parse_from(["t9", url.as_str(), "-o", output, "-l", "2"]);
+
+        // Одну из страниц с общим путем записать нельзя: ошибка учитывается, загрузка продолжается
+        let stats = mirror(&args).await.unwrap();
+        assert_eq!(stats.pages, 2);
+        assert_eq!(stats.errors, 1);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_robots_other_domain() {
+        let (port, fixture) = serve_fixture().await;
+        let directory = tempfile::tempdir().unwrap();
+        let url = format!("http://127.0.0.1:{port}/api/");
+        let output = directory.path().to_str().unwrap();
+        let argv = ["t9", url.as_str(), "-o", output, "--domains", "localhost"];
+        let stats = mirror(&Args::parse_from(argv)).await.unwrap();
+
+        // robots.txt другого хоста загружен до замены ссылок: запрещенная ссылка осталась внешней
+        let root = directory.path().join(format!("127.0.0.1_{port}"));
+        let index = fs::read_to_string(root.join("api/index.html")).unwrap();
+        assert!(index.contains(&format!(
+            r#"href="http://localhost:{port}/docs/private.html""#
+        )));
+        assert_eq!(fixture.requests("/robots.txt"), 2);
+        assert_eq!(stats.blocked, 1);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_level() {
+        let (directory, port) = run_mirror(&[]).await;
+        let root = directory.path().join(format!("127.0.0.1_{port}"));
+        let index = fs::read_to_string(root.join("docs/index.html")).unwrap();
+
+        // -l 1: только начальная страница и ее ресурсы
+        assert!(!root.join("docs/page.html").exists());
+        assert!(root.join("static/logo@v=2.png").exists());
+        assert!(index.contains(&format!(
+            r#"href="http://127.0.0.1:{port}/docs/page.html#top""#
+        )));
+    }
+
+    #[test]
+    fn test_robots() {
+        let robots = Robots::parse(
+            "# comment\n\
+             User-agent: other\n\
+             Disallow: /\n\
+             \n\
+             User-agent: *\n\
+             User-agent: t9\n\
+             Disallow: /private\n\
+             Allow: /private/public\n\
+             Disallow: /*.pdf$\n\
+             Disallow: /search*q=\n\
+             Crawl-delay: 0.5\n",
+        );
+        assert_eq!(robots.crawl_delay, Some(Duration::from_millis(500)));
+
+        let allows =
+            |path: &str| robots.allows(&Url::parse("http://a.com").unwrap().join(path).unwrap());
+        assert!(allows("/"));
+        assert!(allows("/public/a.html"));
+        assert!(!allows("/private"));
+        assert!(!allows("/private/a.html"));
+        assert!(allows("/private/public/a.html"));
+        assert!(!allows("/docs/a.pdf"));
+        assert!(allows("/docs/a.pdf.html"));
+        assert!(!allows("/search?x=1&q=2"));
+        assert!(allows("/search?x=1"));
+
+        assert_eq!(
+            Robots::parse("User-agent: other\nDisallow: /\n"),
+            Robots::default()
+        );
+        assert!(!Robots::parse("User-agent: T9\nDisallow: /\n")
+            .allows(&Url::parse("http://a.com/x").unwrap()));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_crawler_politeness() {
+        let (port, fixture) = serve_fixture().await;
+        let directory = tempfile::tempdir().unwrap();
+        let stats = mirror_fixture(
+            port,
+            directory.path(),
+            &["-l", "2", "-j", "8", "--per-host", "2"],
+        )
+        .await;
+        let root = directory.path().join(format!("127.0.0.1_{port}"));
+
+        // Не больше --per-host одновременных запросов к хосту, robots.txt загружен один раз
+        assert!(fixture.max_active.load(Ordering::SeqCst) <= 2);
+        assert_eq!(fixture.requests("/robots.txt"), 1);
+
+        // robots.txt: запрещенная страница не загружается и ссылка на нее остается внешней
+        assert_eq!(fixture.requests("/docs/private.html"), 0);
+        assert!(root.join("docs/private-ok/index.html").exists());
+        let index = fs::read_to_string(root.join("docs/index.html")).unwrap();
+        assert!(index.contains(&format!(
+            r#"href="http://127.0.0.1:{port}/docs/private.html""#
+        )));
+
+        // Повтор после ответа 503
+        assert_eq!(fixture.requests("/static/flaky.js"), 2);
+        assert_eq!(
+            fs::read_to_string(root.join("static/flaky.js")).unwrap(),
+            "flaky()"
+        );
+
+        // Итоги: docs/, page.html, sub/, about, private-ok
+        assert_eq!(stats.pages, 5);
+        assert_eq!(stats.resources, 3);
+        assert_eq!(stats.retries, 1);
+        assert_eq!(stats.blocked, 1);
+        assert_eq!(stats.errors, 0);
+
+        // --ignore-robots
+        let directory = tempfile::tempdir().unwrap();
+        let stats = mirror_fixture(port, directory.path(), &["-l", "2", "--ignore-robots"]).await;
+        assert_eq!(stats.blocked, 0);
+        assert_eq!(fixture.requests("/docs/private.html"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_crawler_continue() {
+        let (port, fixture) = serve_fixture().await;
+        let directory = tempfile::tempdir().unwrap();
+
+        // Первый запуск - один уровень, состояние сохранено
+        let stats = mirror_fixture(port, directory.path(), &["-l", "1"]).await;
+        assert_eq!(stats.pages, 1);
+        let state = State::load(&directory.path().join(STATE_FILE))
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.level, 1);
+        assert!(state
+            .pending
+            .iter()
+            .any(|url| url.ends_with("/docs/page.html")));
+
+        // Продолжение: загруженное не запрашивается повторно, BFS идет со второго уровня
+        let stats = mirror_fixture(port, directory.path(), &["-l", "2", "--continue"]).await;
+        assert_eq!(fixture.requests("/docs/"), 1);
+        assert_eq!(fixture.requests("/static/style.css"), 1);
+        assert_eq!(fixture.requests("/docs/page.html"), 1);
+        assert_eq!(stats.pages, 4);
+        let root = directory.path().join(format!("127.0.0.1_{port}"));
+        assert!(root.join("docs/sub/index.html").exists());
+
+        // Все уровни загружены - продолжать нечего
+        let stats = mirror_fixture(port, directory.path(), &["-l", "2", "--continue"]).await;
+        assert_eq!(stats.pages, 0);
+    }
+
+    #[test]
+    fn test_file_names() {
+        let disposition = |value| content_disposition_file_name(value);
+        assert_eq!(
+            disposition("attachment; filename=report.pdf"),
+            Some("report.pdf".to_string())
+        );
+        assert_eq!(
+            disposition(r#"attachment; FileName="a; \"b\".txt""#),
+            Some("a; _b_.txt".to_string())
+        );
+        assert_eq!(
+            disposition("attachment; filename=a.txt; filename*=utf-8''%D1%84%20x.txt"),
+            Some("ф x.txt".to_string())
+        );
+        assert_eq!(
+            disposition(r#"attachment; filename="../../etc/passwd""#),
+            Some("passwd".to_string())
+        );
+        assert_eq!(disposition(r#"attachment; filename="..""#), None);
+        assert_eq!(disposition("inline"), None);
+
+        let name = |url: &str| url_file_name(&Url::parse(url).unwrap());
+        assert_eq!(
+            name("http://a.com/x/file%20name.tar.gz?v=1"),
+            "file name.tar.gz"
+        );
+        assert_eq!(name("http://a.com/x/dir/"), "dir");
+        assert_eq!(name("http://a.com"), "index.html");
+        assert_eq!(percent_decode("100%25 %zz %4"), "100% %zz %4");
+    }
+
+    #[test]
+    fn test_progress() {
+        assert_eq!(format_eta(75), "1:15");
+        assert_eq!(format_eta(3725), "1:02:05");
+        assert_eq!(
+            format_progress(512 * 1024, Some(1024 * 1024), 128.0 * 1024.0),
+            "[===============>              ]  50% 512.0 KiB / 1.0 MiB  128.0 KiB/s  ETA 0:04"
+        );
+        assert_eq!(
+            format_progress(2048, Some(2048), 0.0),
+            "[==============================] 100% 2.0 KiB / 2.0 KiB  0 B/s  ETA --:--"
+        );
+        assert_eq!(format_progress(10, None, 5.0), "10 B  5 B/s");
+
+        assert_eq!(
+            parse_content_range("bytes 10-99/100"),
+            Some((Some(10), Some(100)))
+        );
+        assert_eq!(parse_content_range("bytes */100"), Some((None, Some(100))));
+        assert_eq!(parse_content_range("bytes 10-99/*"), Some((Some(10), None)));
+        assert_eq!(parse_content_range("items 1-2/3"), None);
+    }
+
+    #[tokio::test]
+    async fn test_single_download() {
+        let (port, _) = serve_fixture().await;
+        let directory = tempfile::tempdir().unwrap();
+
+        let path = download_fixture(port, directory.path(), "/files/data.bin", &[])
+            .await
+            .unwrap();
+        assert_eq!(path, directory.path().join("data.bin"));
+        assert_eq!(fs::read(&path).unwrap(), file_data());
+
+        // Имя из Content-Disposition
+        let path = download_fixture(port, directory.path(), "/files/report", &[])
+            .await
+            .unwrap();
+        assert_eq!(path, directory.path().join("отчет.txt"));
+        assert_eq!(fs::read_to_string(path).unwrap(), "report");
+
+        // Имя из --output-document
+        let target = directory.path().join("named.bin");
+        let options = ["-O", target.to_str().unwrap()];
+        download_fixture(port, directory.path(), "/files/data.bin", &options)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&target).unwrap(), file_data());
+    }
+
+    #[tokio::test]
+    async fn test_single_resume() {
+        let (port, fixture) = serve_fixture().await;
+        let directory = tempfile::tempdir().unwrap();
+        let data = file_data();
+
+        // Докачивается только недостающая часть
+        let path = directory.path().join("data.bin");
+        fs::write(&path, &data[..30_000]).unwrap();
+        download_fixture(port, directory.path(), "/files/data.bin", &["-c"])
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), data);
+        assert_eq!(*fixture.ranges.lock().unwrap(), ["bytes=30000-"]);
+
+        // Файл загружен полностью (416) - не изменяется
+        download_fixture(port, directory.path(), "/files/data.bin", &["-c"])
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), data);
+        assert_eq!(fixture.ranges.lock().unwrap().len(), 2);
+
+        // Сервер без поддержки Range - загрузка с начала
+        let path = directory.path().join("plain.bin");
+        fs::write(&path, b"garbage").unwrap();
+        download_fixture(port, directory.path(), "/files/plain.bin", &["-c"])
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_single_incomplete() {
+        let port = serve_truncated().await;
+        let directory = tempfile::tempdir().unwrap();
+        let err = download_fixture(port, directory.path(), "/partial.bin", &[])
+            .await
+            .unwrap_err();
+        assert!(err.contains("incomplete download"), "{err}");
+        assert!(err.contains("of 100 bytes"), "{err}");
+        // Загруженная часть остается для --continue
+        let length = fs::metadata(directory.path().join("partial.bin"))
+            .unwrap()
+            .len();
+        assert!(length <= 40);
+    }
 }